@@ -1,7 +1,9 @@
 package com.media.image;
 
 import java.io.File;
+import java.nio.ByteBuffer;
 import java.util.HashMap;
+import java.util.List;
 import java.util.Map;
 
 /**
@@ -19,6 +21,11 @@ import java.util.Map;
  * <li>ZW[800] 按照宽度800压缩</li>
  * <li>ZH[800] 按照高度800压缩</li>
  * <li>ZS[3] 按照百分之三十缩放</li>
+ * <li>ZF300[X200不填默认左值] 等比缩放到300x200以内</li>
+ * <li>ZC300[X200不填默认左值] 等比缩放到覆盖300x200, 然后居中裁剪</li>
+ * <li>ZE300[X200不填默认左值] 拉伸到300x200</li>
+ * <li>ZP300[X200不填默认左值] 等比缩放到300x200以内, 然后使用背景色 bg 填充到300x200</li>
+ * <li>ZM300[X200不填默认左值] 等比缩放到覆盖300x200, 不裁剪</li>
  * </ul>
  *
  * <ul>
@@ -29,6 +36,47 @@ import java.util.Map;
  * <li>CLC300[X400不填默认左值] 左居中截取图片</li>
  * <li>CRC300[X400不填默认左值] 右居中截取图片</li>
  * <li>CCC300[X400不填默认左值] 完全居中截取图片</li>
+ * <li>CTC300[X400不填默认左值] 上居中截取图片</li>
+ * <li>CBC300[X400不填默认左值] 下居中截取图片</li>
+ * <li>CO10X20X300X400 从坐标 (10,20) 截取图片300x400</li>
+ * <li>CCC50PX30P 数值后缀 P 表示当前图片的百分比, 居中截取宽50%高30%</li>
+ * </ul>
+ *
+ * <ul>
+ * <li>R90 顺时针旋转90度 (R180、R270 同理, R-90 逆时针)</li>
+ * <li>R45 顺时针旋转任意角度, 空白区域使用背景色 bg 填充 (默认透明)</li>
+ * <li>FH 水平翻转</li>
+ * <li>FV 垂直翻转</li>
+ * <li>B5 高斯模糊, sigma 0-50</li>
+ * </ul>
+ *
+ * <ul>
+ * <li>type=webp 输出格式: jpeg、png、gif、avif、bmp、tiff、webp (不填按照 to 的扩展名, 默认 png)</li>
+ * <li>GIF、WebP 动图输出为 webp、gif 时逐帧处理并保留帧时长和循环次数, 输出其他格式只保留第一帧</li>
+ * <li>quality=75 图片质量 0-100 (jpeg、webp、avif)</li>
+ * <li>max_bytes=51200 目标大小 (字节), 自动查找不超过该大小的最高质量, 最低质量仍然超出时缩小图片 (jpeg、webp、avif)</li>
+ * <li>compression=6 PNG 压缩级别 0-9</li>
+ * <li>interlace=true JPEG 渐进式编码, 其他格式忽略 (strict=true 时抛出 UnsupportedFormatException)</li>
+ * <li>lossless、near_lossless、method、alpha_quality 等 WebP 编码参数 (见 {@link WebPParams})</li>
+ * <li>speed=6 AVIF 编码速度 1-10</li>
+ * <li>icc=srgb 色彩管理: 按照内嵌 ICC 配置文件 (没有时视为 sRGB, CMYK JPEG 同样支持) 转换到 srgb (默认)、display-p3、adobe-rgb 或 file: 开头的 ICC 文件路径 (如 icc=file:/data/icc/custom.icc), none 不转换, 其他名称抛出 MediaParseException</li>
+ * <li>icc_embed=true 在输出图片中写入输出色彩空间的 ICC 配置文件 (默认只有非 sRGB 输出时写入), 转换后不再保留源配置文件</li>
+ * <li>strip=all 元数据保留策略: all 删除全部 (默认)、gps-only 只删除 GPS、keep-icc 只保留 ICC、keep-all 保留 EXIF/ICC/XMP, 只支持 jpeg、png、webp, 其他格式使用保留策略时抛出 UnsupportedFormatException</li>
+ * <li>bg=ffffff 背景色 (rrggbb、rrggbbaa 或 transparent), 输出格式不支持透明 (jpeg、bmp) 时合成到该颜色, 默认白色 (背景色透明的部分同样按照白色合成)</li>
+ * <li>upscale=true 允许放大图片 (默认不放大)</li>
+ * <li>clamp=true 裁剪超出图片时截断到图片范围 (默认跳过)</li>
+ * <li>auto_orient=false 关闭按照 EXIF 方向自动矫正 (默认开启, 在样式参数之前执行)</li>
+ * <li>strict=true 严格模式, 样式参数无效 (未知操作、数字错误、裁剪超出图片) 时抛出异常, 默认跳过无效参数</li>
+ * <li>decode_cache=true 缓存解码后的源图片, 同一张图片生成多个样式时只解码一次 (默认按照 {@link Media#setDecodeCache(boolean, long)})</li>
+ * <li>style=feed_cover 使用预设参数 (见 {@link Media#setPresets(File)}), 同时传入的其他参数覆盖预设中的同名参数</li>
+ * </ul>
+ *
+ * <ul>
+ * <li>兼容腾讯云数据万象参数, 例如 imageMogr2/thumbnail/300x/crop/200x200/gravity/center/format/webp/quality/80,
+ * 支持 thumbnail、crop (gravity、dx、dy)、cut、rotate、flip、format、quality、interlace、strip、blur、auto-orient, 多个 imageMogr2 参数按照请求中的顺序处理</li>
+ * <li>兼容阿里云 OSS 参数, 例如 x-oss-process=image/resize,m_fill,w_300,h_200/format,webp,
+ * 支持 resize、crop、rotate、format、quality、interlace、blur、auto-orient</li>
+ * <li>转换后的样式操作追加在 args 之后, 裁剪超出图片时截断到图片范围; rotate 为 360 时不旋转, blur 的 σ 不超过半径</li>
  * </ul>
  *
  * @author JNI.
@@ -99,9 +147,49 @@ public class ImageStyle extends Media {
      */
     public static native byte[] handle(byte[] image, String queryParams);
 
+    /**
+     * 处理图片, 输入输出都不经过 Java 堆.
+     *
+     * @param image       图片 (direct ByteBuffer, 读取 position 到 limit 之间的数据).
+     * @param queryParams 参数.
+     * @return 处理结果, 使用完成后关闭 (如果传入To 参数则不会返回).
+     */
+    public static native MediaBuffer handleByBuffer(ByteBuffer image, String queryParams);
+
+    /**
+     * 处理图片并返回结果 (包含目标大小模式最终使用的质量).
+     *
+     * @param image       图片.
+     * @param queryParams 参数.
+     * @return 处理结果 (如果传入To 参数则不包含图片).
+     */
+    public static native StyleResult handleWithResult(byte[] image, String queryParams);
+
+    /**
+     * 一次解码生成多个输出 (例如多个宽度、多种格式), 相同的样式操作前缀只处理一次.
+     *
+     * @param image    图片.
+     * @param variants 每个输出的参数, 例如 args=ZW800&type=webp&quality=75.
+     * @return 处理结果, 顺序与 variants 相同.
+     */
+    public static StyleResult[] variants(byte[] image, List<String> variants) {
+        return handleVariants(image, "", variants.toArray(new String[0]));
+    }
+
+    /**
+     * 一次解码生成多个输出.
+     *
+     * @param image       图片 (为空时读取 from 参数).
+     * @param queryParams 公共参数 (from、auto_orient、icc、strip 以及所有输出的默认参数).
+     * @param variants    每个输出的参数 (args、type、quality、to 等, strip 只能在公共参数中指定), to 支持 {index}、{width}、{height}、{type} 占位符 (至少包含一个, 否则抛出 MediaParseException).
+     * @return 处理结果, 顺序与 variants 相同 (如果传入To 参数则不包含图片).
+     */
+    public static native StyleResult[] handleVariants(byte[] image, String queryParams, String[] variants);
+
 
     /**
      * 元信息.
+     * <br/>width/height 为按照 EXIF 方向矫正后的宽高, rawWidth/rawHeight 为原始宽高, orientation 为 EXIF 方向.
      *
      * @param file 文件.
      * @return 信息.
@@ -120,4 +208,31 @@ public class ImageStyle extends Media {
 
 
     public static native String imageMetadata(String file);
+
+    /**
+     * 完整元信息 (JSON).
+     * <br/>包含 format、fileSize、width/height、rawWidth/rawHeight、colorType、bitDepth、hasAlpha、frameCount、
+     * progressive、lossless (GIF 为 false)、dpi {x, y}、iccProfile (ICC 配置文件描述)、
+     * exif {make, model, dateTime, orientation, gps {latitude, longitude, altitude}}, 无法读取的字段为 null.
+     *
+     * @param file 文件.
+     * @return JSON.
+     */
+    public static String metadataJson(File file) {
+        return imageMetadataJson(file.getAbsolutePath());
+    }
+
+    /**
+     * 完整元信息 (JSON), 字段同 {@link #metadataJson(File)}.
+     *
+     * @param image 图片.
+     * @return JSON.
+     */
+    public static String metadataJson(byte[] image) {
+        return imageMetadataJsonByBytes(image);
+    }
+
+    public static native String imageMetadataJson(String file);
+
+    public static native String imageMetadataJsonByBytes(byte[] image);
 }
@@ -0,0 +1,17 @@
+package com.media.image;
+
+/**
+ * 超出资源限制 (文件大小、宽高、像素数、帧数等, 见 MediaLimits).
+ *
+ * @author JNI.
+ */
+public class LimitExceededException extends MediaException {
+    public LimitExceededException(String message) {
+        super("LIMIT_EXCEEDED", message);
+    }
+
+    @Override
+    public boolean isClientError() {
+        return true;
+    }
+}
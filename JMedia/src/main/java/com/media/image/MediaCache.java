@@ -0,0 +1,59 @@
+package com.media.image;
+
+import lombok.Data;
+
+import java.net.URLEncoder;
+import java.nio.charset.StandardCharsets;
+import java.util.Objects;
+
+/**
+ * 处理结果缓存, 相同的输入和参数直接返回缓存结果, 同时到达的相同请求只处理一次.
+ *
+ * @author JNI.
+ */
+@Data
+public class MediaCache {
+    /**
+     * 缓存目录 (不存在时创建), 为空时关闭缓存.
+     */
+    private String dir;
+    /**
+     * 缓存最大字节数, 超出时淘汰最近最少使用的结果, 默认 1GB.
+     */
+    private Long maxBytes;
+
+    /**
+     * 缓存到指定目录.
+     *
+     * @param dir      缓存目录.
+     * @param maxBytes 缓存最大字节数.
+     * @return 缓存.
+     */
+    public static MediaCache of(String dir, long maxBytes) {
+        final MediaCache cache = new MediaCache();
+        cache.setDir(dir);
+        cache.setMaxBytes(maxBytes);
+        return cache;
+    }
+
+    /**
+     * 关闭缓存.
+     *
+     * @return 缓存.
+     */
+    public static MediaCache disabled() {
+        return new MediaCache();
+    }
+
+    @Override
+    public String toString() {
+        final StringBuilder sb = new StringBuilder();
+        if (Objects.nonNull(dir) && !dir.isEmpty()) {
+            sb.append("dir=").append(URLEncoder.encode(dir, StandardCharsets.UTF_8)).append("&");
+        }
+        if (Objects.nonNull(maxBytes)) {
+            sb.append("max_bytes=").append(maxBytes).append("&");
+        }
+        return sb.toString();
+    }
+}
@@ -0,0 +1,17 @@
+package com.media.image;
+
+/**
+ * 图片解码、编码异常 (图片损坏或无法识别).
+ *
+ * @author JNI.
+ */
+public class MediaDecodeException extends MediaException {
+    public MediaDecodeException(String message) {
+        super("DECODE_ERROR", message);
+    }
+
+    @Override
+    public boolean isClientError() {
+        return true;
+    }
+}
@@ -0,0 +1,210 @@
+package com.media.image;
+
+import java.io.File;
+import java.lang.ref.Cleaner;
+import java.lang.ref.Reference;
+import java.nio.ByteBuffer;
+
+/**
+ * native 内存中已解码的图片, 多次处理、编码时不再重复解码.
+ * <br/>图片不可修改, 样式处理返回新的图片, 可以在多个线程中同时使用; 使用完成后调用 {@link #close()} 释放, 未关闭时由 Cleaner 释放.
+ * <br/>关闭后继续使用抛出 {@link MediaException}.
+ *
+ * <pre>
+ * try (MediaImage image = MediaImage.open(bytes);
+ *      MediaImage cropped = image.style("CO0X0X800X800")) {
+ *     String metadata = image.metadataJson();
+ *     byte[] small = cropped.style("ZW200").encode("type=webp&quality=75");
+ * }
+ * </pre>
+ *
+ * @author JNI.
+ */
+public final class MediaImage extends Media implements AutoCloseable {
+    private static final Cleaner CLEANER = Cleaner.create();
+
+    /**
+     * native 图片 id, 使用 id 的 native 调用结束前通过 reachabilityFence 保持可达, 避免 Cleaner 提前释放图片.
+     */
+    private final long handle;
+    private final Cleaner.Cleanable cleanable;
+
+    private MediaImage(long handle) {
+        this.handle = handle;
+        this.cleanable = CLEANER.register(this, new Release(handle));
+    }
+
+    /**
+     * 解码图片.
+     *
+     * @param image 图片.
+     * @return 图片.
+     */
+    public static MediaImage open(byte[] image) {
+        return open(image, "");
+    }
+
+    /**
+     * 解码图片.
+     *
+     * @param image       图片.
+     * @param queryParams 参数 (auto_orient、icc、icc_embed、strip).
+     * @return 图片.
+     */
+    public static MediaImage open(byte[] image, String queryParams) {
+        return new MediaImage(decode(image, queryParams));
+    }
+
+    /**
+     * 解码图片.
+     *
+     * @param image       图片 (direct ByteBuffer, 读取 position 到 limit 之间的数据).
+     * @param queryParams 参数 (auto_orient、icc、icc_embed、strip).
+     * @return 图片.
+     */
+    public static MediaImage open(ByteBuffer image, String queryParams) {
+        return new MediaImage(decodeByBuffer(image, queryParams));
+    }
+
+    /**
+     * 解码图片文件.
+     *
+     * @param file        文件.
+     * @param queryParams 参数 (auto_orient、icc、icc_embed、strip).
+     * @return 图片.
+     */
+    public static MediaImage open(File file, String queryParams) {
+        return new MediaImage(decodeFile(file.getPath(), queryParams));
+    }
+
+    /**
+     * 样式处理.
+     *
+     * @param args 样式参数 (见 {@link ImageStyle}).
+     * @return 处理后的图片.
+     */
+    public MediaImage style(String args) {
+        return styleWithParams("args=" + args);
+    }
+
+    /**
+     * 样式处理.
+     *
+     * @param queryParams 参数 (args、strict、bg、clamp、upscale).
+     * @return 处理后的图片.
+     */
+    public MediaImage styleWithParams(String queryParams) {
+        try {
+            return new MediaImage(apply(handle, queryParams));
+        } finally {
+            Reference.reachabilityFence(this);
+        }
+    }
+
+    /**
+     * 编码.
+     *
+     * @param queryParams 参数 (type、quality、max_bytes、to 等, 同 {@link ImageStyle#handle(byte[], String)}), strip 在打开时指定, 不能在编码时修改.
+     * @return 二进制 (如果传入To 参数则不会返回).
+     */
+    public byte[] encode(String queryParams) {
+        try {
+            return encodeWithResult(handle, queryParams).getData();
+        } finally {
+            Reference.reachabilityFence(this);
+        }
+    }
+
+    /**
+     * 编码并返回结果 (包含目标大小模式最终使用的质量).
+     *
+     * @param queryParams 参数.
+     * @return 处理结果.
+     */
+    public StyleResult encodeWithResult(String queryParams) {
+        try {
+            return encodeWithResult(handle, queryParams);
+        } finally {
+            Reference.reachabilityFence(this);
+        }
+    }
+
+    /**
+     * 源图片元信息 (JSON, 同 {@link ImageStyle#metadataJson(byte[])}).
+     *
+     * @return JSON.
+     */
+    public String metadataJson() {
+        try {
+            return metadata(handle);
+        } finally {
+            Reference.reachabilityFence(this);
+        }
+    }
+
+    /**
+     * 当前宽度.
+     *
+     * @return 宽度.
+     */
+    public int getWidth() {
+        try {
+            return dimensions(handle)[0];
+        } finally {
+            Reference.reachabilityFence(this);
+        }
+    }
+
+    /**
+     * 当前高度.
+     *
+     * @return 高度.
+     */
+    public int getHeight() {
+        try {
+            return dimensions(handle)[1];
+        } finally {
+            Reference.reachabilityFence(this);
+        }
+    }
+
+    @Override
+    public void close() {
+        cleanable.clean();
+    }
+
+    /**
+     * 释放图片, 不能引用 MediaImage 本身.
+     */
+    private static final class Release implements Runnable {
+        /**
+         * 待释放的 native 图片 id.
+         */
+        private final long handle;
+
+        private Release(long handle) {
+            this.handle = handle;
+        }
+
+        @Override
+        public void run() {
+            release(handle);
+        }
+    }
+
+    private static native long decode(byte[] image, String queryParams);
+
+    private static native long decodeByBuffer(ByteBuffer image, String queryParams);
+
+    private static native long decodeFile(String path, String queryParams);
+
+    private static native long apply(long handle, String queryParams);
+
+    private static native StyleResult encodeWithResult(long handle, String queryParams);
+
+    private static native String metadata(long handle);
+
+    private static native int[] dimensions(long handle);
+
+    private static native void release(long handle);
+}
@@ -0,0 +1,17 @@
+package com.media.image;
+
+/**
+ * 文件读写异常.
+ *
+ * @author JNI.
+ */
+public class MediaIoException extends MediaException {
+    public MediaIoException(String message) {
+        super("IO_ERROR", message);
+    }
+
+    @Override
+    public boolean isClientError() {
+        return false;
+    }
+}
@@ -0,0 +1,17 @@
+package com.media.image;
+
+/**
+ * 参数解析异常 (查询参数、样式参数格式错误).
+ *
+ * @author JNI.
+ */
+public class MediaParseException extends MediaException {
+    public MediaParseException(String message) {
+        super("PARSE_ERROR", message);
+    }
+
+    @Override
+    public boolean isClientError() {
+        return true;
+    }
+}
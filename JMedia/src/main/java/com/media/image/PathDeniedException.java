@@ -0,0 +1,17 @@
+package com.media.image;
+
+/**
+ * 路径不在沙箱允许的目录内或已禁止路径读写 (见 MediaSandbox).
+ *
+ * @author JNI.
+ */
+public class PathDeniedException extends MediaException {
+    public PathDeniedException(String message) {
+        super("PATH_DENIED", message);
+    }
+
+    @Override
+    public boolean isClientError() {
+        return true;
+    }
+}
@@ -0,0 +1,17 @@
+package com.media.image;
+
+/**
+ * 二维码生成异常 (例如内容过长).
+ *
+ * @author JNI.
+ */
+public class QrCodeException extends MediaException {
+    public QrCodeException(String message) {
+        super("QR_CODE_ERROR", message);
+    }
+
+    @Override
+    public boolean isClientError() {
+        return true;
+    }
+}
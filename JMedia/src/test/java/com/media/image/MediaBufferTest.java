@@ -0,0 +1,48 @@
+package com.media.image;
+
+import org.junit.jupiter.api.Test;
+
+import java.lang.reflect.Constructor;
+import java.nio.ByteBuffer;
+
+import static org.junit.jupiter.api.Assertions.assertArrayEquals;
+import static org.junit.jupiter.api.Assertions.assertEquals;
+import static org.junit.jupiter.api.Assertions.assertThrows;
+
+/**
+ * MediaBuffer 关闭后的访问.
+ *
+ * @author JNI.
+ */
+class MediaBufferTest {
+
+    /**
+     * 不经过 native 创建, 测试期间视图保持可达, Cleaner 不会执行.
+     */
+    private static MediaBuffer create(byte[] data) throws ReflectiveOperationException {
+        final ByteBuffer buffer = ByteBuffer.allocateDirect(data.length);
+        buffer.put(data).flip();
+        final Constructor<MediaBuffer> constructor = MediaBuffer.class.getDeclaredConstructor(ByteBuffer.class, long.class, long.class);
+        constructor.setAccessible(true);
+        return constructor.newInstance(buffer, 0L, (long) data.length);
+    }
+
+    @Test
+    void closeWhileViewIsLive() throws ReflectiveOperationException {
+        final MediaBuffer buffer = create(new byte[]{1, 2, 3, 4});
+        final ByteBuffer view = buffer.getBuffer();
+        buffer.close();
+        buffer.close();
+
+        assertThrows(IllegalStateException.class, buffer::getBuffer);
+        assertThrows(IllegalStateException.class, buffer::size);
+        assertThrows(IllegalStateException.class, buffer::toBytes);
+
+        // 关闭前取得的视图引用原 ByteBuffer, 内存仍然有效
+        System.gc();
+        final byte[] bytes = new byte[view.remaining()];
+        view.get(bytes);
+        assertArrayEquals(new byte[]{1, 2, 3, 4}, bytes);
+        assertEquals(4, view.capacity());
+    }
+}
@@ -3,6 +3,10 @@ package com.media.image;
 import com.billbear.tools.common.LoadLibs;
 import lombok.extern.log4j.Log4j2;
 
+import java.io.File;
+import java.io.IOException;
+import java.nio.charset.StandardCharsets;
+import java.nio.file.Files;
 import java.util.List;
 
 @Log4j2
@@ -13,4 +17,97 @@ public class Media {
         final String rootPath = LoadLibs.getRootPath();
         LoadLibs.load(rootPath, libs, libPath -> log.info("System Load .so(dll) <== {}", libPath));
     }
+
+    /**
+     * 设置全局解码资源限制, 超出限制时抛出 LimitExceededException.
+     *
+     * @param limits 限制.
+     */
+    public static void setLimits(MediaLimits limits) {
+        configureLimits(limits.toString());
+    }
+
+    /**
+     * 设置全局解码资源限制.
+     *
+     * @param queryParams 参数 (max_input_bytes=...&max_width=...&).
+     */
+    public static native void configureLimits(String queryParams);
+
+    /**
+     * 设置全局文件沙箱, 路径不在允许的目录内或关闭路径读写时抛出 PathDeniedException.
+     *
+     * @param sandbox 沙箱.
+     */
+    public static void setSandbox(MediaSandbox sandbox) {
+        configureSandbox(sandbox.toString());
+    }
+
+    /**
+     * 设置全局文件沙箱.
+     *
+     * @param queryParams 参数 (path_io=...&roots=...&).
+     */
+    public static native void configureSandbox(String queryParams);
+
+    /**
+     * 设置全局样式预设, 替换已有预设, 处理图片时通过参数 style=名称 引用, 调用时传入的其他参数覆盖预设中的同名参数.
+     *
+     * @param file 预设配置文件 (.json 或 .toml).
+     * @throws IOException 读取文件失败.
+     */
+    public static void setPresets(File file) throws IOException {
+        configurePresets(Files.readString(file.toPath(), StandardCharsets.UTF_8));
+    }
+
+    /**
+     * 设置全局样式预设, 不存在的预设抛出 MediaParseException 并列出所有预设.
+     *
+     * @param config 预设配置, JSON ({"feed_cover": "args=ZW800ACCC300&type=webp"}) 或 TOML.
+     */
+    public static native void configurePresets(String config);
+
+    /**
+     * 设置全局处理结果缓存 (ImageStyle、QrCode), 替换已有配置, 目录中已有的结果继续使用.
+     *
+     * @param cache 缓存.
+     */
+    public static void setCache(MediaCache cache) {
+        configureCache(cache.toString());
+    }
+
+    /**
+     * 设置全局处理结果缓存.
+     *
+     * @param queryParams 参数 (dir=...&max_bytes=...&), dir 为空时关闭缓存.
+     */
+    public static native void configureCache(String queryParams);
+
+    /**
+     * 缓存统计.
+     *
+     * @return 统计.
+     */
+    public static MediaCacheStats getCacheStats() {
+        return new MediaCacheStats(cacheStats());
+    }
+
+    private static native long[] cacheStats();
+
+    /**
+     * 设置全局解码缓存, 按照文件路径 (大小、修改时间) 或图片内容缓存解码后的源图片 (静态图片).
+     *
+     * @param enabled  全局开启, 单次调用可以通过参数 decode_cache 覆盖.
+     * @param maxBytes 已解码图片的最大像素内存 (字节), 超出时淘汰最近最少使用的图片.
+     */
+    public static void setDecodeCache(boolean enabled, long maxBytes) {
+        configureDecodeCache("enabled=" + enabled + "&max_bytes=" + maxBytes + "&");
+    }
+
+    /**
+     * 设置全局解码缓存.
+     *
+     * @param queryParams 参数 (enabled=...&max_bytes=...&).
+     */
+    public static native void configureDecodeCache(String queryParams);
 }
@@ -0,0 +1,98 @@
+package com.media.image;
+
+import java.lang.ref.Cleaner;
+import java.nio.ByteBuffer;
+
+/**
+ * native 内存中的处理结果 (direct ByteBuffer), 不占用 Java 堆.
+ * <br/>内存由 Cleaner 在 ByteBuffer 及其所有视图都被回收后释放, {@link #close()} 只释放对 ByteBuffer 的引用,
+ * 关闭前取得的视图仍然可以安全读取; 关闭后 {@link #getBuffer()}、{@link #size()}、{@link #toBytes()} 抛出 IllegalStateException.
+ *
+ * @author JNI.
+ */
+public final class MediaBuffer implements AutoCloseable {
+    private static final Cleaner CLEANER = Cleaner.create();
+
+    /**
+     * 关闭后为 null, 视图通过 attachment 引用原 ByteBuffer, 不会提前释放内存.
+     */
+    private volatile ByteBuffer buffer;
+
+    /**
+     * 由 native 创建.
+     *
+     * @param buffer  direct ByteBuffer.
+     * @param address 内存地址.
+     * @param length  长度.
+     */
+    private MediaBuffer(ByteBuffer buffer, long address, long length) {
+        this.buffer = buffer;
+        CLEANER.register(buffer, new Free(address, length));
+    }
+
+    /**
+     * 图片数据 (只读).
+     *
+     * @return direct ByteBuffer.
+     */
+    public ByteBuffer getBuffer() {
+        return open().asReadOnlyBuffer();
+    }
+
+    /**
+     * 数据长度.
+     *
+     * @return 字节数.
+     */
+    public int size() {
+        return open().capacity();
+    }
+
+    /**
+     * 复制到 Java 堆.
+     *
+     * @return 二进制.
+     */
+    public byte[] toBytes() {
+        final ByteBuffer buffer = open();
+        final byte[] bytes = new byte[buffer.capacity()];
+        buffer.duplicate().get(bytes);
+        return bytes;
+    }
+
+    @Override
+    public void close() {
+        buffer = null;
+    }
+
+    /**
+     * 读取 ByteBuffer, 局部变量保持可达, 其他线程同时关闭不会释放内存.
+     */
+    private ByteBuffer open() {
+        final ByteBuffer buffer = this.buffer;
+        if (buffer == null) {
+            throw new IllegalStateException("media buffer closed");
+        }
+        return buffer;
+    }
+
+    /**
+     * 释放内存, 不能引用 ByteBuffer 本身.
+     */
+    private static final class Free implements Runnable {
+        private final long address;
+        private final long length;
+
+        private Free(long address, long length) {
+            this.address = address;
+            this.length = length;
+        }
+
+        @Override
+        public void run() {
+            free(address, length);
+        }
+    }
+
+    private static native void free(long address, long length);
+}
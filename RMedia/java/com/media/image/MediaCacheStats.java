@@ -0,0 +1,45 @@
+package com.media.image;
+
+import lombok.Data;
+
+/**
+ * 处理结果缓存统计 (进程启动后累计).
+ *
+ * @author JNI.
+ */
+@Data
+public class MediaCacheStats {
+    /**
+     * 命中次数.
+     */
+    private final long hits;
+    /**
+     * 未命中 (重新处理) 次数.
+     */
+    private final long misses;
+    /**
+     * 等待相同请求处理结果的次数.
+     */
+    private final long coalesced;
+    /**
+     * 淘汰次数.
+     */
+    private final long evictions;
+    /**
+     * 当前结果数量.
+     */
+    private final long entries;
+    /**
+     * 当前结果总字节数.
+     */
+    private final long bytes;
+
+    public MediaCacheStats(long[] values) {
+        this.hits = values[0];
+        this.misses = values[1];
+        this.coalesced = values[2];
+        this.evictions = values[3];
+        this.entries = values[4];
+        this.bytes = values[5];
+    }
+}
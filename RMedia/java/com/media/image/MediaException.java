@@ -0,0 +1,40 @@
+package com.media.image;
+
+/**
+ * 图片处理异常, 所有 native 方法失败时抛出该异常或其子类.
+ *
+ * @author JNI.
+ */
+public class MediaException extends RuntimeException {
+    /**
+     * 错误码.
+     */
+    private final String code;
+
+    public MediaException(String message) {
+        this("MEDIA_ERROR", message);
+    }
+
+    protected MediaException(String code, String message) {
+        super(message);
+        this.code = code;
+    }
+
+    /**
+     * 错误码, 例如 IO_ERROR、DECODE_ERROR、LIMIT_EXCEEDED.
+     *
+     * @return 错误码.
+     */
+    public String getCode() {
+        return code;
+    }
+
+    /**
+     * 是否由请求参数或输入图片导致 (对应 HTTP 4xx), 否则为服务端错误 (HTTP 5xx).
+     *
+     * @return 客户端错误.
+     */
+    public boolean isClientError() {
+        return false;
+    }
+}
@@ -0,0 +1,69 @@
+package com.media.image;
+
+import lombok.Data;
+
+import java.util.Objects;
+
+/**
+ * 解码资源限制 (未设置的参数使用默认值).
+ *
+ * @author JNI.
+ */
+@Data
+public class MediaLimits {
+    /**
+     * 输入文件最大字节数, 默认 20MB.
+     */
+    private Long maxInputBytes;
+    /**
+     * 图片最大宽度, 默认 16384.
+     */
+    private Integer maxWidth;
+    /**
+     * 图片最大高度, 默认 16384.
+     */
+    private Integer maxHeight;
+    /**
+     * 图片最大像素数, 默认 1 亿.
+     */
+    private Long maxPixels;
+    /**
+     * 解码最大内存分配 (字节), 动图按照所有帧计算, 默认 512MB.
+     */
+    private Long maxAlloc;
+    /**
+     * 动图最大帧数, 默认 1000.
+     */
+    private Integer maxFrames;
+    /**
+     * 单次处理最多的样式操作数量, 默认 5.
+     */
+    private Integer maxOps;
+
+    @Override
+    public String toString() {
+        final StringBuilder sb = new StringBuilder();
+        if (Objects.nonNull(maxInputBytes)) {
+            sb.append("max_input_bytes=").append(maxInputBytes).append("&");
+        }
+        if (Objects.nonNull(maxWidth)) {
+            sb.append("max_width=").append(maxWidth).append("&");
+        }
+        if (Objects.nonNull(maxHeight)) {
+            sb.append("max_height=").append(maxHeight).append("&");
+        }
+        if (Objects.nonNull(maxPixels)) {
+            sb.append("max_pixels=").append(maxPixels).append("&");
+        }
+        if (Objects.nonNull(maxAlloc)) {
+            sb.append("max_alloc=").append(maxAlloc).append("&");
+        }
+        if (Objects.nonNull(maxFrames)) {
+            sb.append("max_frames=").append(maxFrames).append("&");
+        }
+        if (Objects.nonNull(maxOps)) {
+            sb.append("max_ops=").append(maxOps).append("&");
+        }
+        return sb.toString();
+    }
+}
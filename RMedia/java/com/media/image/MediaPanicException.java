@@ -0,0 +1,12 @@
+package com.media.image;
+
+/**
+ * native 代码内部错误 (panic), 异常信息包含调用的方法.
+ *
+ * @author JNI.
+ */
+public class MediaPanicException extends MediaException {
+    public MediaPanicException(String message) {
+        super("PANIC", message);
+    }
+}
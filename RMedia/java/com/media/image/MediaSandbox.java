@@ -0,0 +1,61 @@
+package com.media.image;
+
+import lombok.Data;
+
+import java.io.File;
+import java.net.URLEncoder;
+import java.nio.charset.StandardCharsets;
+import java.util.List;
+import java.util.Objects;
+
+/**
+ * 文件沙箱, 限制 from、to、logo_path、poster_path、icc 等路径参数可以访问的目录.
+ *
+ * @author JNI.
+ */
+@Data
+public class MediaSandbox {
+    /**
+     * 允许按照路径读写文件, 默认 true, 关闭后只能通过二进制处理.
+     */
+    private Boolean pathIo;
+    /**
+     * 允许访问的根目录 (必须存在), 为空时不限制; 路径会解析符号链接后检查.
+     */
+    private List<String> roots;
+
+    /**
+     * 只允许访问指定目录.
+     *
+     * @param roots 根目录.
+     * @return 沙箱.
+     */
+    public static MediaSandbox of(String... roots) {
+        final MediaSandbox sandbox = new MediaSandbox();
+        sandbox.setRoots(List.of(roots));
+        return sandbox;
+    }
+
+    /**
+     * 禁止按照路径读写文件.
+     *
+     * @return 沙箱.
+     */
+    public static MediaSandbox disabled() {
+        final MediaSandbox sandbox = new MediaSandbox();
+        sandbox.setPathIo(false);
+        return sandbox;
+    }
+
+    @Override
+    public String toString() {
+        final StringBuilder sb = new StringBuilder();
+        if (Objects.nonNull(pathIo)) {
+            sb.append("path_io=").append(pathIo).append("&");
+        }
+        if (Objects.nonNull(roots) && !roots.isEmpty()) {
+            sb.append("roots=").append(URLEncoder.encode(String.join(File.pathSeparator, roots), StandardCharsets.UTF_8)).append("&");
+        }
+        return sb.toString();
+    }
+}
@@ -1,5 +1,7 @@
 package com.media.image;
 
+import java.nio.ByteBuffer;
+
 /**
  * 二维码.
  *
@@ -25,4 +27,14 @@ public class QrCode extends Media {
      * @return 二维码.
      */
     public static native byte[] generate(String queryParams, byte[] logo, byte[] poster);
+
+    /**
+     * 生成, 输入输出都不经过 Java 堆.
+     *
+     * @param queryParams 参数.
+     * @param logo        logo 文件 (direct ByteBuffer).
+     * @param poster      海报文件 (direct ByteBuffer).
+     * @return 二维码, 使用完成后关闭.
+     */
+    public static native MediaBuffer generateByBuffer(String queryParams, ByteBuffer logo, ByteBuffer poster);
 }
@@ -43,7 +43,7 @@ public class QrCodeParams {
      */
     private Integer logoSize;
     /**
-     * Logo 的间距.
+     * Logo 的间距 (最大 500).
      */
     private Integer logoMargin;
     /**
@@ -62,6 +62,10 @@ public class QrCodeParams {
      * 海报二维码: Y.
      */
     private Integer posterQrCodeY;
+    /**
+     * Logo、海报按照 EXIF 方向矫正 (默认开启).
+     */
+    private Boolean autoOrient;
 
     public QrCodeParams(String text) {
         this.text = text;
@@ -103,6 +107,9 @@ public class QrCodeParams {
         if (Objects.nonNull(posterQrCodeY)) {
             sb.append("poster_qr_code_y=").append(posterQrCodeY).append("&");
         }
+        if (Objects.nonNull(autoOrient)) {
+            sb.append("auto_orient=").append(autoOrient).append("&");
+        }
         return sb.toString();
     }
 }
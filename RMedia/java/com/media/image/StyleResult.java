@@ -0,0 +1,35 @@
+package com.media.image;
+
+import lombok.Data;
+
+/**
+ * 图片样式处理结果.
+ *
+ * @author JNI.
+ */
+@Data
+public class StyleResult {
+    /**
+     * 图片 (传入 to 参数时为空).
+     */
+    private final byte[] data;
+    /**
+     * 目标大小模式 (max_bytes) 最终使用的质量, 其他情况为空.
+     */
+    private final Float quality;
+    /**
+     * 输出宽度.
+     */
+    private final int width;
+    /**
+     * 输出高度.
+     */
+    private final int height;
+
+    public StyleResult(byte[] data, Float quality, int width, int height) {
+        this.data = data;
+        this.quality = quality;
+        this.width = width;
+        this.height = height;
+    }
+}
@@ -0,0 +1,17 @@
+package com.media.image;
+
+/**
+ * 不支持的图片格式.
+ *
+ * @author JNI.
+ */
+public class UnsupportedFormatException extends MediaException {
+    public UnsupportedFormatException(String message) {
+        super("UNSUPPORTED_FORMAT", message);
+    }
+
+    @Override
+    public boolean isClientError() {
+        return true;
+    }
+}
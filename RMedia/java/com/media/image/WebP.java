@@ -1,7 +1,10 @@
 package com.media.image;
 
+import java.nio.ByteBuffer;
+
 /**
  * WebP 格式转换.
+ * <br/>编码时 GIF、WebP 动图保留所有帧; 解码时动图输出为 .gif 文件保留所有帧, 其他情况只保留第一帧.
  *
  * @author JNI.
  */
@@ -24,6 +27,35 @@ public final class WebP extends Media {
      */
     public static native byte[] encodedByBytes(byte[] from, float quality);
 
+    /**
+     * 编码, 输入输出都不经过 Java 堆.
+     *
+     * @param from    文件源 (direct ByteBuffer, 读取 position 到 limit 之间的数据).
+     * @param quality 质量(0-100).
+     * @return WebP 图片, 使用完成后关闭.
+     */
+    public static native MediaBuffer encodedByBuffer(ByteBuffer from, float quality);
+
+    /**
+     * 编码.
+     *
+     * @param from   文件源.
+     * @param params 编码参数.
+     * @return WebP 图片.
+     */
+    public static byte[] encodedByBytes(byte[] from, WebPParams params) {
+        return encodedByBytesWithParams(from, params.toString());
+    }
+
+    /**
+     * 编码.
+     *
+     * @param from        文件源.
+     * @param queryParams 编码参数 (见 {@link WebPParams}).
+     * @return WebP 图片.
+     */
+    public static native byte[] encodedByBytesWithParams(byte[] from, String queryParams);
+
     /**
      * 解码.
      *
@@ -0,0 +1,126 @@
+package com.media.image;
+
+import lombok.Data;
+
+import java.util.Objects;
+
+/**
+ * WebP 编码参数 (未设置的参数使用 libwebp 默认值).
+ *
+ * @author JNI.
+ */
+@Data
+public class WebPParams {
+    /**
+     * 质量 (0-100), 无损模式下表示压缩力度.
+     */
+    private Float quality;
+    /**
+     * 无损编码.
+     */
+    private Boolean lossless;
+    /**
+     * 近无损预处理级别 (0-100, 100 表示关闭), 仅无损模式有效.
+     */
+    private Integer nearLossless;
+    /**
+     * 压缩方法 (0-6), 越大越慢压缩率越高.
+     */
+    private Integer method;
+    /**
+     * 透明通道质量 (0-100).
+     */
+    private Integer alphaQuality;
+    /**
+     * 空间噪声整形强度 (0-100).
+     */
+    private Integer snsStrength;
+    /**
+     * 去块滤波强度 (0-100).
+     */
+    private Integer filterStrength;
+    /**
+     * 目标 PSNR (dB).
+     */
+    private Float targetPsnr;
+    /**
+     * 目标大小 (字节).
+     */
+    private Integer targetSize;
+    /**
+     * 多线程编码.
+     */
+    private Boolean multithreading;
+    /**
+     * 元数据保留策略 (all、gps-only、keep-icc、keep-all), 默认删除全部.
+     */
+    private String strip;
+    /**
+     * 输出色彩空间 (srgb、display-p3、adobe-rgb、none 或 ICC 文件路径), 默认转换到 sRGB.
+     */
+    private String icc;
+    /**
+     * 写入输出色彩空间的 ICC 配置文件.
+     */
+    private Boolean iccEmbed;
+
+    public WebPParams() {
+        this.quality = 75f;
+    }
+
+    /**
+     * 无损编码参数.
+     *
+     * @return 参数.
+     */
+    public static WebPParams lossless() {
+        final WebPParams params = new WebPParams();
+        params.setLossless(true);
+        return params;
+    }
+
+    @Override
+    public String toString() {
+        final StringBuilder sb = new StringBuilder();
+        if (Objects.nonNull(quality)) {
+            sb.append("quality=").append(quality).append("&");
+        }
+        if (Objects.nonNull(lossless)) {
+            sb.append("lossless=").append(lossless).append("&");
+        }
+        if (Objects.nonNull(nearLossless)) {
+            sb.append("near_lossless=").append(nearLossless).append("&");
+        }
+        if (Objects.nonNull(method)) {
+            sb.append("method=").append(method).append("&");
+        }
+        if (Objects.nonNull(alphaQuality)) {
+            sb.append("alpha_quality=").append(alphaQuality).append("&");
+        }
+        if (Objects.nonNull(snsStrength)) {
+            sb.append("sns_strength=").append(snsStrength).append("&");
+        }
+        if (Objects.nonNull(filterStrength)) {
+            sb.append("filter_strength=").append(filterStrength).append("&");
+        }
+        if (Objects.nonNull(targetPsnr)) {
+            sb.append("target_psnr=").append(targetPsnr).append("&");
+        }
+        if (Objects.nonNull(targetSize)) {
+            sb.append("target_size=").append(targetSize).append("&");
+        }
+        if (Objects.nonNull(multithreading)) {
+            sb.append("multithreading=").append(multithreading).append("&");
+        }
+        if (Objects.nonNull(strip)) {
+            sb.append("strip=").append(strip).append("&");
+        }
+        if (Objects.nonNull(icc)) {
+            sb.append("icc=").append(icc).append("&");
+        }
+        if (Objects.nonNull(iccEmbed)) {
+            sb.append("icc_embed=").append(iccEmbed).append("&");
+        }
+        return sb.toString();
+    }
+}
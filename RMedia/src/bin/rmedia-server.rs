@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Instant, UNIX_EPOCH};
+
+use clap::Parser;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use media_jni::error::MediaError;
+use media_jni::image_cache::{CacheConfig, CacheKey};
+use media_jni::image_decode_cache::DecodeCacheConfig;
+use media_jni::image_encode::OutputFormat;
+use media_jni::image_limits::Limits;
+use media_jni::image_presets::Presets;
+use media_jni::image_sandbox::Sandbox;
+use media_jni::{image_cache, image_decode_cache, image_limits, image_presets, image_qr_code, image_sandbox, image_style};
+
+/// HTTP 图片处理服务, 访问根目录下的图片: `GET /path/to/img.jpg?args=ZW200&type=webp`,
+/// 生成二维码: `GET /_qr?text=...`.
+#[derive(Parser)]
+#[command(name = "rmedia-server", version)]
+struct Cli {
+    /// 图片根目录, 只允许访问该目录下的文件.
+    #[arg(short, long)]
+    root: PathBuf,
+
+    /// 监听地址.
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    bind: String,
+
+    /// 同时处理的请求数, 默认为 CPU 核数.
+    #[arg(short, long)]
+    workers: Option<usize>,
+
+    /// 响应头 Cache-Control 的 max-age (秒).
+    #[arg(long, default_value_t = 86400)]
+    max_age: u64,
+
+    /// 资源限制, 参数同 Media.setLimits (例如 max_width=8000&max_pixels=40000000).
+    #[arg(long)]
+    limits: Option<String>,
+
+    /// 样式预设配置文件 (JSON 或 TOML), 通过参数 style=名称 引用.
+    #[arg(long)]
+    presets: Option<PathBuf>,
+
+    /// 处理结果缓存目录, 默认不缓存.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// 处理结果缓存最大字节数, 超出时淘汰最近最少使用的结果.
+    #[arg(long, default_value_t = 1024 * 1024 * 1024)]
+    cache_max_bytes: u64,
+
+    /// 解码缓存最大像素内存 (字节), 同一张图片生成多个样式时只解码一次, 默认不缓存.
+    #[arg(long)]
+    decode_cache_max_bytes: Option<u64>,
+}
+
+/// 处理结果.
+struct Output {
+    data: Vec<u8>,
+    content_type: &'static str,
+}
+
+/// 请求处理上下文.
+struct Service {
+    root: PathBuf,
+    cache_control: String,
+
+    /// ETag 的公共部分: 资源限制和样式预设, 配置不同的结果互不共用.
+    etag_key: CacheKey,
+}
+
+impl Service {
+    fn handle(&self, request: &Request) -> Result<Response<io::Cursor<Vec<u8>>>, (u16, String)> {
+        if !matches!(request.method(), Method::Get | Method::Head) {
+            return Err((405, "method not allowed".to_string()));
+        }
+        let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+        let path = percent_encoding::percent_decode_str(path).decode_utf8()
+            .map_err(|_| (400, "invalid path encoding".to_string()))?;
+        let mut query_params_map = image_style::query_map(query).map_err(|error| error_status(&error))?;
+        for key in ["to", "from"] {
+            if query_params_map.contains_key(key) {
+                return Err((400, format!("parameter `{}` not allowed", key)));
+            }
+        }
+
+        // 未修改时不再处理
+        let file = if path == "/_qr" { None } else { Some(self.resolve(&path)?) };
+        let metadata = match &file {
+            None => None,
+            Some(file) => {
+                let metadata = file.metadata().map_err(|error| error_status(&error.into()))?;
+                if !metadata.is_file() {
+                    return Err((404, "not found".to_string()));
+                }
+                Some(metadata)
+            }
+        };
+        let etag = etag(&self.etag_key, &path, &query_params_map, metadata.as_ref());
+        let not_modified = request.headers().iter()
+            .any(|header| header.field.equiv("If-None-Match") && header.value.as_str().split(',').any(|value| value.trim() == etag));
+        if not_modified {
+            return Ok(self.headers(Response::from_data(Vec::new()).with_status_code(304), &etag));
+        }
+
+        let output = match file {
+            None => self.qr_code(query).map_err(|error| error_status(&error))?,
+            Some(file) => self.image(&file, &mut query_params_map).map_err(|error| error_status(&error))?,
+        };
+        let response = Response::from_data(output.data)
+            .with_header(header("Content-Type", output.content_type));
+        Ok(self.headers(response, &etag))
+    }
+
+    /// 将请求路径解析为根目录下的文件, 拒绝 `..` 等路径.
+    fn resolve(&self, path: &str) -> Result<PathBuf, (u16, String)> {
+        let relative = Path::new(path.trim_start_matches('/'));
+        if relative.as_os_str().is_empty() || relative.components().any(|component| !matches!(component, Component::Normal(_))) {
+            return Err((404, "not found".to_string()));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    /// 处理图片, 未传入参数时返回原图; 未指定 type 时保持源文件格式.
+    fn image(&self, file: &Path, query_params_map: &mut HashMap<String, String>) -> Result<Output, MediaError> {
+        let buffer = image_sandbox::read_file(file)?;
+        if query_params_map.is_empty() {
+            let content_type = image::guess_format(&buffer).map(|format| format.to_mime_type()).unwrap_or("application/octet-stream");
+            return Ok(Output { data: buffer, content_type });
+        }
+        let format = match query_params_map.get("type") {
+            Some(value) => OutputFormat::parse(value)?,
+            None => OutputFormat::from_path(file).unwrap_or(OutputFormat::Png),
+        };
+        query_params_map.insert("type".to_string(), format.extension().to_string());
+        query_params_map.insert("from".to_string(), file.to_string_lossy().to_string());
+        let query_params = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(query_params_map.iter())
+            .finish();
+        let data = image_style::edit(Some(&buffer), &query_params)?
+            .ok_or(MediaError::Error("image not generated".to_string()))?;
+        Ok(Output { data, content_type: format.mime() })
+    }
+
+    /// 生成二维码 (PNG), logo_path、poster_path 只允许根目录下的文件.
+    fn qr_code(&self, query: &str) -> Result<Output, MediaError> {
+        let data = image_qr_code::new(query.to_string(), None, None)?.generate_png()?;
+        Ok(Output { data, content_type: OutputFormat::Png.mime() })
+    }
+
+    fn headers(&self, response: Response<io::Cursor<Vec<u8>>>, etag: &str) -> Response<io::Cursor<Vec<u8>>> {
+        response.with_header(header("ETag", etag))
+            .with_header(header("Cache-Control", &self.cache_control))
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("invalid header")
+}
+
+/// 错误对应的 HTTP 状态码, 与 Java 异常一致: 客户端错误 (isClientError) 为 4xx, 其他为 500.
+fn error_status(error: &MediaError) -> (u16, String) {
+    let status = match error {
+        MediaError::IoError(error) if error.kind() == io::ErrorKind::NotFound => 404,
+        MediaError::PathDenied(_) => 403,
+        MediaError::LimitExceeded(_) | MediaError::QRCodeImageError(image::ImageError::Limits(_)) => 413,
+        MediaError::UnsupportedFormat(_) | MediaError::QRCodeImageError(image::ImageError::Unsupported(_)) => 415,
+        MediaError::IoError(_) | MediaError::QRCodeImageError(image::ImageError::IoError(_)) => 500,
+        MediaError::DecodeError(_) | MediaError::QRCodeImageError(_) => 422,
+        MediaError::ParseError(_) | MediaError::ParseIntError(_) | MediaError::ParseFloatError(_) | MediaError::StyleError { .. }
+        | MediaError::InvalidParam(_) | MediaError::UnknownPreset(_) | MediaError::QRCodeError(_) => 400,
+        MediaError::Error(_) | MediaError::JniError(_) | MediaError::Panic(_) => 500,
+    };
+    (status, error.to_string())
+}
+
+/// ETag 的公共部分: 资源限制和样式预设.
+fn etag_key(presets: Option<&str>) -> CacheKey {
+    CacheKey::new("http")
+        .bytes(format!("{:?}", image_limits::limits()).as_bytes())
+        .optional(presets.map(str::as_bytes))
+}
+
+/// 按照路径、参数 (排序后)、文件大小和修改时间计算 ETag.
+fn etag(base: &CacheKey, path: &str, query_params_map: &HashMap<String, String>, metadata: Option<&fs::Metadata>) -> String {
+    let mut key = base.clone().bytes(path.as_bytes()).params(query_params_map, &[]);
+    if let Some(metadata) = metadata {
+        let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map(|time| time.as_nanos());
+        key = key.bytes(&metadata.len().to_le_bytes()).optional(modified.map(u128::to_le_bytes).as_ref().map(|value| value.as_slice()));
+    }
+    format!("\"{}\"", key.finish())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(error) = run(cli) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), MediaError> {
+    if let Some(limits) = &cli.limits {
+        image_limits::set_limits(Limits::new(&image_style::query_map(limits)?)?);
+    }
+    let presets = cli.presets.as_ref().map(fs::read_to_string).transpose()?;
+    if let Some(presets) = &presets {
+        image_presets::set_presets(Presets::parse(presets)?);
+    }
+    let etag_key = etag_key(presets.as_deref());
+    if cli.cache_dir.is_some() {
+        image_cache::configure(CacheConfig { dir: cli.cache_dir.clone(), max_bytes: cli.cache_max_bytes })?;
+    }
+    if let Some(max_bytes) = cli.decode_cache_max_bytes {
+        image_decode_cache::set_config(DecodeCacheConfig { enabled: true, max_bytes });
+    }
+    // 所有按照路径读写的参数 (logo_path、poster_path、icc) 只允许访问根目录
+    let root = cli.root.canonicalize()
+        .map_err(|error| MediaError::Error(format!("root `{}` invalid: {}", cli.root.display(), error)))?;
+    if !root.is_dir() {
+        return Err(MediaError::Error(format!("root `{}` is not a directory", cli.root.display())));
+    }
+    image_sandbox::set_sandbox(Sandbox { path_io: true, roots: vec![root.clone()] });
+
+    let server = Arc::new(Server::http(&cli.bind)
+        .map_err(|error| MediaError::Error(format!("bind `{}` fail: {}", cli.bind, error)))?);
+    let service = Arc::new(Service { root, cache_control: format!("public, max-age={}", cli.max_age), etag_key });
+    let workers = cli.workers.unwrap_or_else(|| thread::available_parallelism().map(|value| value.get()).unwrap_or(4)).max(1);
+    eprintln!("rmedia-server listening on {}, root {}, {} workers", cli.bind, service.root.display(), workers);
+
+    // 固定数量的处理线程, 超出的请求在队列中等待
+    let handles: Vec<_> = (0..workers).map(|_| {
+        let server = server.clone();
+        let service = service.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let start = Instant::now();
+                let response = panic::catch_unwind(AssertUnwindSafe(|| service.handle(&request)))
+                    .unwrap_or_else(|_| Err((500, "internal error".to_string())));
+                let status = match &response {
+                    Ok(response) => response.status_code().0,
+                    Err((status, _)) => *status,
+                };
+                eprintln!("{} {} {} {}ms", request.method(), request.url(), status, start.elapsed().as_millis());
+                let result = match response {
+                    Ok(response) => request.respond(response),
+                    Err((status, message)) => request.respond(Response::from_string(message).with_status_code(status)),
+                };
+                if let Err(error) = result {
+                    eprintln!("error: respond fail: {}", error);
+                }
+            }
+        })
+    }).collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(error: MediaError) -> u16 {
+        error_status(&error).0
+    }
+
+    #[test]
+    fn client_errors_are_4xx() {
+        assert_eq!(status(MediaError::InvalidParam("quality".to_string())), 400);
+        assert_eq!(status("x".parse::<u32>().unwrap_err().into()), 400);
+        assert_eq!(status(MediaError::UnknownPreset("thumb".to_string())), 400);
+        assert_eq!(status(MediaError::PathDenied("/etc/passwd".to_string())), 403);
+        assert_eq!(status(io::Error::from(io::ErrorKind::NotFound).into()), 404);
+        assert_eq!(status(MediaError::LimitExceeded("width".to_string())), 413);
+        assert_eq!(status(MediaError::UnsupportedFormat("bmp".to_string())), 415);
+        assert_eq!(status(MediaError::DecodeError("truncated".to_string())), 422);
+    }
+
+    #[test]
+    fn internal_errors_are_5xx() {
+        assert_eq!(status(MediaError::Error("encode fail".to_string())), 500);
+        assert_eq!(status(MediaError::Panic("boom".to_string())), 500);
+        assert_eq!(status(io::Error::from(io::ErrorKind::PermissionDenied).into()), 500);
+    }
+
+    #[test]
+    fn etag_stable() {
+        let base = etag_key(Some("thumb=imageMogr2/thumbnail/100x"));
+        let params = |query: &str| image_style::query_map(query).unwrap();
+        let metadata = fs::metadata("Cargo.toml").unwrap();
+
+        let first = etag(&base, "/a.jpg", &params("args=imageMogr2/thumbnail/100x&type=webp"), Some(&metadata));
+        let second = etag(&base, "/a.jpg", &params("type=webp&args=imageMogr2/thumbnail/100x"), Some(&metadata));
+        assert_eq!(first, second);
+        assert!(first.starts_with('"') && first.ends_with('"'));
+
+        assert_ne!(first, etag(&base, "/b.jpg", &params("args=imageMogr2/thumbnail/100x&type=webp"), Some(&metadata)));
+        assert_ne!(first, etag(&base, "/a.jpg", &params("args=imageMogr2/thumbnail/200x&type=webp"), Some(&metadata)));
+        assert_ne!(first, etag(&base, "/a.jpg", &params("args=imageMogr2/thumbnail/100x&type=webp"), None));
+        assert_ne!(first, etag(&etag_key(None), "/a.jpg", &params("args=imageMogr2/thumbnail/100x&type=webp"), Some(&metadata)));
+    }
+}
@@ -0,0 +1,404 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use serde_json::Value;
+
+use media_jni::error::MediaError;
+use media_jni::image_color::ColorOptions;
+use media_jni::image_encode::{EncodeOptions, OutputFormat};
+use media_jni::image_limits::Limits;
+use media_jni::image_presets::Presets;
+use media_jni::image_strip::StripPolicy;
+use media_jni::{image_encode, image_limits, image_metadata, image_presets, image_qr_code, image_sandbox, image_style, image_webp};
+
+/// 图片处理、WebP 编解码、二维码生成命令行工具.
+#[derive(Parser)]
+#[command(name = "rmedia", version)]
+struct Cli {
+    /// 资源限制, 参数同 Media.setLimits (例如 max_width=8000&max_pixels=40000000).
+    #[arg(long, global = true)]
+    limits: Option<String>,
+
+    /// 样式预设配置文件 (JSON 或 TOML), 通过参数 style=名称 引用.
+    #[arg(long, global = true)]
+    presets: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 按照样式参数处理图片 (参数同 ImageStyle.handle).
+    Style {
+        /// 样式操作 (例如 ZW200AR90), 覆盖参数中的 args.
+        #[arg(short, long)]
+        args: Option<String>,
+
+        /// 处理参数 (例如 type=webp&quality=80, 或 x-oss-process=image/resize,w_200), 未指定 type 时按照输出文件扩展名确定.
+        #[arg(short, long, default_value = "")]
+        params: String,
+
+        #[command(flatten)]
+        io: Io,
+    },
+
+    /// WebP 编码、解码.
+    Webp {
+        #[command(subcommand)]
+        command: WebpCommand,
+    },
+
+    /// 生成二维码 (PNG), 参数同 QrCode.generate.
+    Qr {
+        /// 二维码参数 (例如 text=https://example.com&size=300).
+        params: String,
+
+        /// Logo 图片文件.
+        #[arg(long)]
+        logo: Option<PathBuf>,
+
+        /// 海报图片文件.
+        #[arg(long)]
+        poster: Option<PathBuf>,
+
+        /// 输出文件, `-` 表示标准输出.
+        #[arg(short, long, default_value = "-")]
+        output: String,
+    },
+
+    /// 输出图片元信息 (每个输入一行 JSON).
+    Info {
+        /// 输入文件, 支持 glob (例如 'images/*.jpg'), `-` 表示标准输入.
+        #[arg(default_value = "-")]
+        inputs: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WebpCommand {
+    /// 编码为 WebP, 动图保留所有帧.
+    Encode {
+        /// 压缩质量 (0 ~ 100), 覆盖参数中的 quality.
+        #[arg(short, long)]
+        quality: Option<f32>,
+
+        /// 编码参数, 同 WebP.encodedByBytesWithParams (例如 lossless=true&strip=none).
+        #[arg(short, long, default_value = "")]
+        params: String,
+
+        #[command(flatten)]
+        io: Io,
+    },
+
+    /// 解码 WebP, 输出格式按照输出文件扩展名确定 (默认 PNG), 动图输出第一帧.
+    Decode {
+        #[command(flatten)]
+        io: Io,
+    },
+}
+
+#[derive(Args)]
+struct Io {
+    /// 输入文件, 支持 glob (例如 'images/*.jpg'), `-` 表示标准输入.
+    #[arg(default_value = "-")]
+    inputs: Vec<String>,
+
+    /// 输出文件, `-` 表示标准输出; 多个输入时为输出目录, 文件名为输入文件名加输出格式扩展名.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+}
+
+/// 输入来源.
+enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Input::Stdin => None,
+            Input::File(path) => Some(path),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Input::Stdin => "-".to_string(),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+
+    fn read(&self) -> Result<Vec<u8>, MediaError> {
+        match self {
+            Input::Stdin => {
+                let mut buffer = Vec::new();
+                io::stdin().lock().read_to_end(&mut buffer)?;
+                image_limits::limits().check_bytes(buffer.len() as u64)?;
+                Ok(buffer)
+            }
+            Input::File(path) => image_sandbox::read_file(path),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(error) = configure(&cli) {
+        eprintln!("error: {}", error);
+        return ExitCode::FAILURE;
+    }
+    let success = match cli.command {
+        Command::Style { args, params, io } => style(args, &params, &io),
+        Command::Webp { command: WebpCommand::Encode { quality, params, io } } => webp_encode(quality, &params, &io),
+        Command::Webp { command: WebpCommand::Decode { io } } => webp_decode(&io),
+        Command::Qr { params, logo, poster, output } => report("qr", qr(params, logo, poster, &output)),
+        Command::Info { inputs } => info(&inputs),
+    };
+    if success { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+/// 设置资源限制和样式预设.
+fn configure(cli: &Cli) -> Result<(), MediaError> {
+    if let Some(limits) = &cli.limits {
+        image_limits::set_limits(Limits::new(&image_style::query_map(limits)?)?);
+    }
+    if let Some(presets) = &cli.presets {
+        image_presets::set_presets(Presets::parse(&fs::read_to_string(presets)?)?);
+    }
+    Ok(())
+}
+
+fn style(args: Option<String>, params: &str, io: &Io) -> bool {
+    let mut query_params_map = match image_style::query_map(params) {
+        Ok(value) => value,
+        Err(error) => return report("params", Err(error)),
+    };
+    if let Some(args) = args {
+        query_params_map.insert("args".to_string(), args);
+    }
+    let format = match query_params_map.get("type") {
+        Some(value) => OutputFormat::parse(value),
+        None => Ok(output_format(io, OutputFormat::Png)),
+    };
+    let format = match format {
+        Ok(value) => value,
+        Err(error) => return report("params", Err(error)),
+    };
+    query_params_map.insert("type".to_string(), format.extension().to_string());
+    let query_params = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(&query_params_map)
+        .finish();
+    batch(io, format.extension(), |input, buffer| {
+        let mut query_params = query_params.clone();
+        if let Some(path) = input.path() {
+            // 文件来源用于识别格式
+            query_params.push_str("&from=");
+            query_params.extend(url::form_urlencoded::byte_serialize(path.to_string_lossy().as_bytes()));
+        }
+        Ok(image_style::edit_with_result(Some(buffer), &query_params)?.data)
+    })
+}
+
+fn webp_encode(quality: Option<f32>, params: &str, io: &Io) -> bool {
+    let options = image_style::query_map(params).and_then(|mut query_params_map| {
+        if let Some(quality) = quality {
+            query_params_map.insert("quality".to_string(), quality.to_string());
+        }
+        Ok((image_webp::WebPOptions::new(&query_params_map)?, StripPolicy::new(&query_params_map)?, ColorOptions::new(&query_params_map)?))
+    });
+    let (options, strip, color) = match options {
+        Ok(value) => value,
+        Err(error) => return report("params", Err(error)),
+    };
+    batch(io, OutputFormat::WebP.extension(), |input, buffer| {
+        Ok(Some(image_webp::encoded_webp_from(buffer, input.path(), &options, strip, &color)?))
+    })
+}
+
+fn webp_decode(io: &Io) -> bool {
+    let format = output_format(io, OutputFormat::Png);
+    batch(io, format.extension(), |_, buffer| {
+        let image = image_webp::decode_webp(buffer.to_vec())?;
+        Ok(Some(image_encode::encode(image, format, &EncodeOptions::default())?))
+    })
+}
+
+fn qr(params: String, logo: Option<PathBuf>, poster: Option<PathBuf>, output: &str) -> Result<(), MediaError> {
+    let logo = logo.map(image_sandbox::read_file).transpose()?;
+    let poster = poster.map(image_sandbox::read_file).transpose()?;
+    let data = image_qr_code::new(params, logo.as_deref(), poster.as_deref())?.generate_png()?;
+    write_output(output, &data)
+}
+
+fn info(patterns: &[String]) -> bool {
+    let inputs = match expand(patterns) {
+        Ok(value) => value,
+        Err(error) => return report("input", Err(error)),
+    };
+    let mut success = true;
+    for input in &inputs {
+        let result = input.read()
+            .and_then(|buffer| image_metadata::read(&buffer, input.path()))
+            .and_then(|metadata| serde_json::to_value(metadata)
+                .map_err(|error| MediaError::Error(format!("metadata serialize fail: {}", error))));
+        match result {
+            Ok(mut value) => {
+                if let (Value::Object(object), Some(path)) = (&mut value, input.path()) {
+                    object.insert("path".to_string(), Value::String(path.display().to_string()));
+                }
+                println!("{}", value);
+            }
+            Err(error) => success &= report(&input.name(), Err(error)),
+        }
+    }
+    success
+}
+
+/// 未指定输出格式时按照输出文件扩展名确定.
+fn output_format(io: &Io, default: OutputFormat) -> OutputFormat {
+    match io.output.as_str() {
+        "-" => default,
+        output if Path::new(output).is_dir() => default,
+        output => OutputFormat::from_path(output).unwrap_or(default),
+    }
+}
+
+/// 展开输入, 包含 glob 通配符时按照匹配的文件处理.
+fn expand(patterns: &[String]) -> Result<Vec<Input>, MediaError> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        if pattern == "-" {
+            inputs.push(Input::Stdin);
+        } else if pattern.contains(['*', '?', '[']) {
+            let paths = glob::glob(pattern).map_err(|error| MediaError::Error(format!("invalid glob `{}`: {}", pattern, error)))?;
+            let length = inputs.len();
+            for path in paths {
+                let path = path.map_err(|error| MediaError::Error(format!("glob `{}` fail: {}", pattern, error)))?;
+                if path.is_file() {
+                    inputs.push(Input::File(path));
+                }
+            }
+            if inputs.len() == length {
+                return Err(MediaError::Error(format!("glob `{}` matched no files", pattern)));
+            }
+        } else {
+            inputs.push(Input::File(PathBuf::from(pattern)));
+        }
+    }
+    Ok(inputs)
+}
+
+/// 逐个处理输入, 单个输入写入输出文件, 多个输入写入输出目录; 失败的输入输出到标准错误后继续处理.
+fn batch<F>(io: &Io, extension: &str, mut handle: F) -> bool
+    where F: FnMut(&Input, &[u8]) -> Result<Option<Vec<u8>>, MediaError> {
+    let inputs = match expand(&io.inputs) {
+        Ok(value) => value,
+        Err(error) => return report("input", Err(error)),
+    };
+    let single = io.inputs.len() == 1 && inputs.len() == 1 && !io.inputs[0].contains(['*', '?', '['])
+        && !Path::new(&io.output).is_dir();
+    if !single {
+        if io.output == "-" {
+            return report("output", Err(MediaError::Error("multiple inputs require --output directory".to_string())));
+        }
+        if let Err(error) = fs::create_dir_all(&io.output) {
+            return report(&io.output, Err(error.into()));
+        }
+    }
+    let mut success = true;
+    for input in &inputs {
+        let result = input.read()
+            .and_then(|buffer| handle(input, &buffer))
+            .and_then(|data| match data {
+                // 参数 to 已写入文件
+                None => Ok(()),
+                Some(data) if single => write_output(&io.output, &data),
+                Some(data) => image_sandbox::write_file(output_path(&io.output, input, extension), &data),
+            });
+        success &= report(&input.name(), result);
+    }
+    success
+}
+
+/// 多个输入时的输出文件: 输出目录下的输入文件名加输出格式扩展名, 标准输入为 stdin.
+fn output_path(dir: &str, input: &Input, extension: &str) -> PathBuf {
+    let stem = input.path().and_then(|path| path.file_stem()).map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "stdin".to_string());
+    Path::new(dir).join(format!("{}.{}", stem, extension))
+}
+
+fn write_output(output: &str, data: &[u8]) -> Result<(), MediaError> {
+    if output == "-" {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(data)?;
+        return Ok(stdout.flush()?);
+    }
+    image_sandbox::write_file(output, data)
+}
+
+/// 输出错误信息, 返回是否成功.
+fn report(name: &str, result: Result<(), MediaError>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(error) => {
+            eprintln!("error: {}: {}", name, error);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn io(output: &str) -> Io {
+        Io { inputs: vec!["-".to_string()], output: output.to_string() }
+    }
+
+    #[test]
+    fn output_format_from_extension() {
+        assert_eq!(output_format(&io("-"), OutputFormat::Png), OutputFormat::Png);
+        assert_eq!(output_format(&io("out.webp"), OutputFormat::Png), OutputFormat::WebP);
+        assert_eq!(output_format(&io("out.unknown"), OutputFormat::Png), OutputFormat::Png);
+        let dir = env::temp_dir();
+        assert_eq!(output_format(&io(&dir.to_string_lossy()), OutputFormat::Png), OutputFormat::Png);
+    }
+
+    #[test]
+    fn expand_inputs() {
+        let dir = env::temp_dir().join(format!("rmedia-cli-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.png"), b"a").unwrap();
+        fs::write(dir.join("b.png"), b"b").unwrap();
+        fs::write(dir.join("c.jpg"), b"c").unwrap();
+        let pattern = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        let inputs = expand(&["-".to_string(), pattern("missing.png")]).unwrap();
+        assert!(matches!(inputs[0], Input::Stdin));
+        assert_eq!(inputs[1].path(), Some(dir.join("missing.png").as_path()));
+
+        let inputs = expand(&[pattern("*.png")]).unwrap();
+        let mut paths: Vec<_> = inputs.iter().filter_map(Input::path).collect();
+        paths.sort();
+        assert_eq!(paths, vec![dir.join("a.png"), dir.join("b.png")]);
+
+        // 目录不作为输入, 没有匹配的文件时报错
+        assert!(expand(&[pattern("*")]).unwrap().iter().all(|input| input.path().is_some_and(Path::is_file)));
+        assert!(expand(&[pattern("*.gif")]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_path_in_dir() {
+        let file = Input::File(PathBuf::from("images/photo.jpg"));
+        assert_eq!(output_path("out", &file, "webp"), Path::new("out").join("photo.webp"));
+        assert_eq!(output_path("out", &Input::Stdin, "png"), Path::new("out").join("stdin.png"));
+    }
+}
@@ -24,6 +24,30 @@ pub enum MediaError {
     #[error("ParseFloatError: {0}")]
     ParseFloatError(#[from] std::num::ParseFloatError),
 
+    #[error("UnsupportedFormat: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("StyleError: {message}, token `{token}` at position {position}")]
+    StyleError { token: String, position: usize, message: String },
+
+    #[error("LimitExceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("PathDenied: {0}")]
+    PathDenied(String),
+
+    #[error("InvalidParam: {0}")]
+    InvalidParam(String),
+
+    #[error("DecodeError: {0}")]
+    DecodeError(String),
+
+    #[error("UnknownPreset: {0}")]
+    UnknownPreset(String),
+
+    #[error("Panic: {0}")]
+    Panic(String),
+
 }
 
 impl From<fast_qr::convert::image::ImageError> for MediaError {
@@ -36,4 +60,35 @@ impl From<&str> for MediaError {
     fn from(value: &str) -> Self {
         MediaError::Error(format!("{:?}", value))
     }
-}
\ No newline at end of file
+}
+
+impl MediaError {
+    /// 对应的 Java 异常类.
+    pub fn exception_class(&self) -> &'static str {
+        match self {
+            MediaError::Error(_) | MediaError::JniError(_) => "com/media/image/MediaException",
+            MediaError::IoError(_) | MediaError::QRCodeImageError(image::ImageError::IoError(_)) => "com/media/image/MediaIoException",
+            MediaError::QRCodeError(_) => "com/media/image/QrCodeException",
+            MediaError::QRCodeImageError(image::ImageError::Limits(_)) | MediaError::LimitExceeded(_) => "com/media/image/LimitExceededException",
+            MediaError::QRCodeImageError(image::ImageError::Unsupported(_)) | MediaError::UnsupportedFormat(_) => "com/media/image/UnsupportedFormatException",
+            MediaError::QRCodeImageError(_) | MediaError::DecodeError(_) => "com/media/image/MediaDecodeException",
+            MediaError::ParseError(_) | MediaError::ParseIntError(_) | MediaError::ParseFloatError(_) | MediaError::StyleError { .. }
+            | MediaError::InvalidParam(_) | MediaError::UnknownPreset(_) => "com/media/image/MediaParseException",
+            MediaError::PathDenied(_) => "com/media/image/PathDeniedException",
+            MediaError::Panic(_) => "com/media/image/MediaPanicException",
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_errors_map_to_client_exceptions() {
+        assert_eq!(MediaError::InvalidParam("x".to_string()).exception_class(), "com/media/image/MediaParseException");
+        assert_eq!(MediaError::DecodeError("x".to_string()).exception_class(), "com/media/image/MediaDecodeException");
+        assert_eq!(MediaError::Error("x".to_string()).exception_class(), "com/media/image/MediaException");
+    }
+}
@@ -0,0 +1,355 @@
+use std::ffi::CStr;
+use std::io::Cursor;
+use std::mem::MaybeUninit;
+use std::ptr::{null, null_mut};
+
+use image::{AnimationDecoder, Delay, DynamicImage, ImageDecoder, ImageFormat, RgbaImage};
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use libwebp_sys::{WebPAnimEncoder, WebPAnimEncoderAdd, WebPAnimEncoderAssemble, WebPAnimEncoderDelete,
+                  WebPAnimEncoderGetError, WebPAnimEncoderNewInternal, WebPAnimEncoderOptions,
+                  WebPAnimEncoderOptionsInitInternal, WebPData, WebPDataClear, WebPGetMuxABIVersion, WebPPicture,
+                  WebPPictureFree, WebPPictureImportRGBA};
+use img_parts::Bytes;
+use img_parts::webp::WebP;
+use webp::{AnimDecoder, BitstreamFeatures};
+
+use crate::error::MediaError;
+use crate::image_encode::{EncodeOptions, OutputFormat};
+use crate::{image_limits, image_strip};
+use crate::image_webp::WebPOptions;
+
+/// 动图帧.
+pub struct Frame {
+    /// 完整画布大小的帧图片.
+    pub image: DynamicImage,
+
+    /// 帧显示时长 (毫秒).
+    pub delay: u32,
+}
+
+/// 动图.
+pub struct Animation {
+    pub frames: Vec<Frame>,
+
+    /// 播放次数 (与 WebP 相同), 0 表示无限循环; GIF 的 NETSCAPE2.0 扩展保存重复次数, 通过 gif_loop_count、gif_repeat 转换.
+    pub loop_count: u32,
+}
+
+impl Animation {
+    /// 对每一帧执行相同的处理.
+    pub fn try_map<F>(self, mut handle: F) -> Result<Animation, MediaError>
+        where F: FnMut(DynamicImage) -> Result<DynamicImage, MediaError> {
+        let mut frames = Vec::with_capacity(self.frames.len());
+        for frame in self.frames {
+            frames.push(Frame { image: handle(frame.image)?, delay: frame.delay });
+        }
+        Ok(Animation { frames, loop_count: self.loop_count })
+    }
+
+    /// 对每一帧执行相同的处理, 保留原动图.
+    pub fn try_map_ref<F>(&self, mut handle: F) -> Result<Animation, MediaError>
+        where F: FnMut(&DynamicImage) -> Result<DynamicImage, MediaError> {
+        let mut frames = Vec::with_capacity(self.frames.len());
+        for frame in &self.frames {
+            frames.push(Frame { image: handle(&frame.image)?, delay: frame.delay });
+        }
+        Ok(Animation { frames, loop_count: self.loop_count })
+    }
+
+    /// 画布宽高 (第一帧).
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.frames.first().map(|frame| (frame.image.width(), frame.image.height())).unwrap_or((0, 0))
+    }
+}
+
+/// 解码动图 (GIF、WebP), 不是动图或只有一帧时返回 None.
+pub fn decode(buffer: &[u8]) -> Result<Option<Animation>, MediaError> {
+    Ok(decode_frames(buffer)?.filter(|animation| animation.frames.len() > 1))
+}
+
+/// 解码 GIF、WebP 动图的所有帧 (可能只有一帧), 其他图片返回 None.
+pub fn decode_frames(buffer: &[u8]) -> Result<Option<Animation>, MediaError> {
+    image_limits::limits().check_bytes(buffer.len() as u64)?;
+    let animation = match image::guess_format(buffer) {
+        Ok(ImageFormat::Gif) => decode_gif(buffer)?,
+        Ok(ImageFormat::WebP) => {
+            match BitstreamFeatures::new(buffer) {
+                Some(features) if features.has_animation() => decode_webp(buffer, &features)?,
+                _ => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(animation))
+}
+
+/// 读取动图帧数 (GIF 图像描述块、WebP ANMF 块数量), 不解码像素, 其他图片为 1.
+pub fn frame_count(buffer: &[u8]) -> usize {
+    let count = match image::guess_format(buffer) {
+        Ok(ImageFormat::Gif) => gif_frame_count(buffer),
+        Ok(ImageFormat::WebP) => webp_chunks(buffer).filter(|(id, _)| id == b"ANMF").count(),
+        _ => 1,
+    };
+    count.max(1)
+}
+
+/// 遍历 GIF 数据块统计图像描述块 (0x2C) 数量, 数据不完整时返回已统计的数量.
+fn gif_frame_count(buffer: &[u8]) -> usize {
+    // 跳过数据子块, 返回结束位置
+    let skip_blocks = |mut position: usize| loop {
+        let size = *buffer.get(position)? as usize;
+        position = position.checked_add(size + 1)?;
+        if size == 0 {
+            return Some(position);
+        }
+    };
+    // 颜色表大小
+    let color_table = |flags: u8| if flags & 0x80 != 0 { 3usize << ((flags & 0x07) + 1) } else { 0 };
+    let Some(&flags) = buffer.get(10) else { return 0 };
+    let mut position = 13 + color_table(flags);
+    let mut count = 0;
+    loop {
+        let next = match buffer.get(position) {
+            Some(0x2C) => buffer.get(position + 9).and_then(|&flags| {
+                count += 1;
+                // 图像描述块 10 字节, 局部颜色表, LZW 最小码长 1 字节
+                skip_blocks(position + 10 + color_table(flags) + 1)
+            }),
+            Some(0x21) => skip_blocks(position + 2),
+            _ => None,
+        };
+        match next {
+            Some(value) => position = value,
+            None => return count,
+        }
+    }
+}
+
+/// 遍历 WebP (RIFF) 顶层块, 返回块类型和内容, 数据不完整时停止.
+pub fn webp_chunks(buffer: &[u8]) -> impl Iterator<Item=([u8; 4], &[u8])> {
+    let start = if buffer.get(0..4) == Some(b"RIFF") && buffer.get(8..12) == Some(b"WEBP") { 12 } else { buffer.len() };
+    riff_chunks(buffer, start)
+}
+
+/// 从 start 开始遍历 RIFF 块 (类型 4 字节、小端长度 4 字节、内容按照偶数长度对齐).
+pub fn riff_chunks(buffer: &[u8], start: usize) -> impl Iterator<Item=([u8; 4], &[u8])> {
+    let mut position = start;
+    std::iter::from_fn(move || {
+        let header = buffer.get(position..position.checked_add(8)?)?;
+        let id = [header[0], header[1], header[2], header[3]];
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let data = buffer.get(position + 8..(position + 8).checked_add(size)?)?;
+        position = (position + 8 + size).checked_add(size & 1)?;
+        Some((id, data))
+    })
+}
+
+/// 编码动图并写入保留的元数据, 只支持 WebP、GIF.
+pub fn encode(animation: &Animation, format: OutputFormat, options: &EncodeOptions) -> Result<Vec<u8>, MediaError> {
+    options.check(format)?;
+    let data = match format {
+        OutputFormat::WebP => encode_webp(animation, &options.webp)?,
+        OutputFormat::Gif => encode_gif(animation)?,
+        _ => return Err(MediaError::UnsupportedFormat(format!("{:?} animation", format))),
+    };
+    image_strip::embed(data, format, &options.metadata)
+}
+
+/// 解码 GIF 动图, 逐帧检查帧数和内存限制.
+fn decode_gif(buffer: &[u8]) -> Result<Animation, MediaError> {
+    let limits = image_limits::limits();
+    let decoder = GifDecoder::new(Cursor::new(buffer))?;
+    let (width, height) = decoder.dimensions();
+    limits.check_dimensions(width, height)?;
+    let mut frames = Vec::new();
+    for frame in decoder.into_frames() {
+        limits.check_frames(frames.len() + 1, width, height)?;
+        let frame = frame?;
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        frames.push(Frame {
+            delay: numerator / denominator.max(1),
+            image: DynamicImage::ImageRgba8(frame.into_buffer()),
+        });
+    }
+    Ok(Animation { frames, loop_count: gif_loop_count(buffer) })
+}
+
+/// 读取 GIF NETSCAPE2.0 扩展中的循环次数, 没有该扩展时只播放一次.
+fn gif_loop_count(buffer: &[u8]) -> u32 {
+    const NETSCAPE: &[u8] = b"NETSCAPE2.0";
+    let repeat = buffer.windows(NETSCAPE.len())
+        .position(|window| window == NETSCAPE)
+        .and_then(|index| buffer.get(index + NETSCAPE.len()..index + NETSCAPE.len() + 4))
+        .filter(|block| block[0] == 3 && block[1] == 1)
+        .map(|block| u16::from_le_bytes([block[2], block[3]]));
+    match repeat {
+        // 没有扩展只播放一次
+        None => 1,
+        Some(0) => 0,
+        Some(value) => value as u32 + 1,
+    }
+}
+
+/// 播放次数转换为 GIF 重复次数, 只播放一次时不写入 NETSCAPE2.0 扩展.
+fn gif_repeat(loop_count: u32) -> Option<Repeat> {
+    match loop_count {
+        0 => Some(Repeat::Infinite),
+        1 => None,
+        value => Some(Repeat::Finite((value - 1).min(u16::MAX as u32) as u16)),
+    }
+}
+
+/// 编码 GIF 动图.
+fn encode_gif(animation: &Animation) -> Result<Vec<u8>, MediaError> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut buffer);
+        if let Some(repeat) = gif_repeat(animation.loop_count) {
+            encoder.set_repeat(repeat)?;
+        }
+        encoder.encode_frames(animation.frames.iter().map(|frame| {
+            image::Frame::from_parts(frame.image.to_rgba8(), 0, 0, Delay::from_numer_denom_ms(frame.delay, 1))
+        }))?;
+    }
+    Ok(buffer)
+}
+
+/// 解码 WebP 动图, libwebp 返回的时间戳为帧结束时间, 解码前按照 ANMF 块数量检查帧数和内存限制.
+fn decode_webp(buffer: &[u8], features: &BitstreamFeatures) -> Result<Animation, MediaError> {
+    let frame_count = WebP::from_bytes(Bytes::copy_from_slice(buffer))
+        .map(|webp| webp.chunks_by_id(*b"ANMF").count())
+        .map_err(|error| MediaError::DecodeError(format!("webp animation decode fail: {}", error)))?;
+    let limits = image_limits::limits();
+    limits.check_dimensions(features.width(), features.height())?;
+    limits.check_frames(frame_count, features.width(), features.height())?;
+    let image = AnimDecoder::new(buffer).decode()
+        .map_err(|error| MediaError::DecodeError(format!("webp animation decode fail: {}", error)))?;
+    let mut frames = Vec::with_capacity(image.len());
+    let mut timestamp = 0;
+    for index in 0..image.len() {
+        let frame = image.get_frame(index).ok_or(MediaError::DecodeError("webp animation frame not found".to_string()))?;
+        let rgba = if frame.get_layout().is_alpha() {
+            RgbaImage::from_raw(frame.width(), frame.height(), frame.get_image().to_vec())
+        } else {
+            image::RgbImage::from_raw(frame.width(), frame.height(), frame.get_image().to_vec())
+                .map(|rgb| DynamicImage::ImageRgb8(rgb).to_rgba8())
+        }.ok_or(MediaError::DecodeError("webp animation frame size mismatch".to_string()))?;
+        frames.push(Frame {
+            image: DynamicImage::ImageRgba8(rgba),
+            delay: (frame.get_time_ms() - timestamp).max(0) as u32,
+        });
+        timestamp = frame.get_time_ms();
+    }
+    Ok(Animation { frames, loop_count: image.loop_count })
+}
+
+/// 编码 WebP 动图, 直接调用 libwebp 以便写入最后一帧的结束时间 (保留最后一帧时长).
+pub fn encode_webp(animation: &Animation, options: &WebPOptions) -> Result<Vec<u8>, MediaError> {
+    let first = animation.frames.first().ok_or(MediaError::Error("animation has no frames".to_string()))?;
+    let (width, height) = (first.image.width(), first.image.height());
+    let config = options.config()?;
+    let error = |message: &str| MediaError::Error(format!("webp animation encode fail: {}", message));
+
+    unsafe {
+        let mut encoder_options = MaybeUninit::<WebPAnimEncoderOptions>::uninit();
+        if WebPAnimEncoderOptionsInitInternal(encoder_options.as_mut_ptr(), WebPGetMuxABIVersion()) == 0 {
+            return Err(error("options init"));
+        }
+        let mut encoder_options = encoder_options.assume_init();
+        encoder_options.anim_params.loop_count = animation.loop_count as i32;
+        let encoder = AnimEncoderGuard(WebPAnimEncoderNewInternal(width as i32, height as i32,
+                                                                  &encoder_options, WebPGetMuxABIVersion()));
+        if encoder.0.is_null() {
+            return Err(error("encoder init"));
+        }
+
+        // 时间戳为帧开始时间, 最后追加空帧写入结束时间
+        let mut timestamp = 0;
+        for frame in &animation.frames {
+            let rgba = frame.image.to_rgba8();
+            if rgba.dimensions() != (width, height) {
+                return Err(error("frames must have the same size"));
+            }
+            let mut picture = WebPPicture::new().map_err(|_| error("picture init"))?;
+            picture.use_argb = 1;
+            picture.width = width as i32;
+            picture.height = height as i32;
+            let imported = WebPPictureImportRGBA(&mut picture, rgba.as_ptr(), width as i32 * 4);
+            let added = imported != 0 && WebPAnimEncoderAdd(encoder.0, &mut picture, timestamp, &config) != 0;
+            WebPPictureFree(&mut picture);
+            if !added {
+                return Err(error(&encoder.error()));
+            }
+            timestamp += frame.delay as i32;
+        }
+        if WebPAnimEncoderAdd(encoder.0, null_mut(), timestamp, null()) == 0 {
+            return Err(error(&encoder.error()));
+        }
+
+        let mut data = MaybeUninit::<WebPData>::zeroed().assume_init();
+        if WebPAnimEncoderAssemble(encoder.0, &mut data) == 0 {
+            return Err(error(&encoder.error()));
+        }
+        let result = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+        WebPDataClear(&mut data);
+        Ok(result)
+    }
+}
+
+/// 释放 libwebp 动图编码器.
+struct AnimEncoderGuard(*mut WebPAnimEncoder);
+
+impl AnimEncoderGuard {
+    /// 读取编码器错误信息.
+    unsafe fn error(&self) -> String {
+        let message = WebPAnimEncoderGetError(self.0);
+        if message.is_null() {
+            return "unknown".to_string();
+        }
+        CStr::from_ptr(message).to_string_lossy().to_string()
+    }
+}
+
+impl Drop for AnimEncoderGuard {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { WebPAnimEncoderDelete(self.0) }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_frames_without_decoding() {
+        let frames = (0..3).map(|_| Frame { image: DynamicImage::new_rgba8(4, 4), delay: 100 }).collect();
+        let gif = encode_gif(&Animation { frames, loop_count: 0 }).unwrap();
+        assert_eq!(frame_count(&gif), 3);
+        assert!(frame_count(&gif[..gif.len() / 2]) < 3);
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0".to_vec();
+        webp.extend_from_slice(&[0; 10]);
+        for _ in 0..2 {
+            webp.extend_from_slice(b"ANMF\x03\0\0\0abc\0");
+        }
+        assert_eq!(frame_count(&webp), 2);
+    }
+
+    #[test]
+    fn loop_count_round_trip() {
+        for loop_count in [0, 1, 3] {
+            let frame = |value| Frame { image: DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, image::Rgba([value, 0, 0, 255]))), delay: 100 };
+            let frames = vec![frame(0), frame(255)];
+            let gif = encode_gif(&Animation { frames, loop_count }).unwrap();
+            let animation = decode_frames(&gif).unwrap().unwrap();
+            assert_eq!(animation.loop_count, loop_count);
+            let webp = encode_webp(&animation, &WebPOptions::default()).unwrap();
+            let animation = decode_frames(&webp).unwrap().unwrap();
+            assert_eq!(animation.loop_count, loop_count);
+            assert_eq!(gif_loop_count(&encode_gif(&animation).unwrap()), loop_count);
+        }
+    }
+}
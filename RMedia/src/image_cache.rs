@@ -0,0 +1,410 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::MediaError;
+
+/// 默认缓存大小 1GB.
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// 结果缓存配置.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// 缓存目录, 为空时关闭缓存.
+    pub dir: Option<PathBuf>,
+
+    /// 缓存最大字节数, 超出时淘汰最近最少使用的结果.
+    pub max_bytes: u64,
+}
+
+impl CacheConfig {
+    /// 关闭缓存 (默认).
+    pub const DISABLED: CacheConfig = CacheConfig { dir: None, max_bytes: DEFAULT_MAX_BYTES };
+
+    /// 读取参数 dir (为空时关闭缓存)、max_bytes (默认 1GB).
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<CacheConfig, MediaError> {
+        Ok(CacheConfig {
+            dir: query_params_map.get("dir").filter(|value| !value.trim().is_empty()).map(PathBuf::from),
+            max_bytes: match query_params_map.get("max_bytes") {
+                None => DEFAULT_MAX_BYTES,
+                Some(value) => value.parse::<u64>()?,
+            },
+        })
+    }
+}
+
+/// 缓存统计.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// 命中次数.
+    pub hits: u64,
+
+    /// 未命中 (重新处理) 次数.
+    pub misses: u64,
+
+    /// 等待相同请求处理结果的次数.
+    pub coalesced: u64,
+
+    /// 淘汰次数.
+    pub evictions: u64,
+
+    /// 当前结果数量.
+    pub entries: u64,
+
+    /// 当前结果总字节数.
+    pub bytes: u64,
+}
+
+/// 缓存键, 依次写入处理类型、参数和输入内容后计算 SHA-256.
+#[derive(Clone)]
+pub struct CacheKey(Sha256);
+
+impl CacheKey {
+    /// kind 为处理类型 (edit、qr_code), 版本号不同的结果互不共用.
+    pub fn new(kind: &str) -> CacheKey {
+        CacheKey(Sha256::new()).bytes(env!("CARGO_PKG_VERSION").as_bytes()).bytes(kind.as_bytes())
+    }
+
+    /// 写入内容 (带长度, 相邻内容不会混淆).
+    pub fn bytes(mut self, value: &[u8]) -> CacheKey {
+        self.0.update((value.len() as u64).to_le_bytes());
+        self.0.update(value);
+        self
+    }
+
+    /// 写入可选内容.
+    pub fn optional(self, value: Option<&[u8]>) -> CacheKey {
+        match value {
+            None => self.bytes(b"\0none"),
+            Some(value) => self.bytes(b"\0some").bytes(value),
+        }
+    }
+
+    /// 按照参数名排序后写入参数, 跳过 skip 中的参数.
+    pub fn params(mut self, query_params_map: &HashMap<String, String>, skip: &[&str]) -> CacheKey {
+        let params = query_params_map.iter()
+            .filter(|(key, _)| !skip.contains(&key.as_str()))
+            .collect::<BTreeMap<&String, &String>>();
+        for (key, value) in params {
+            self = self.bytes(key.as_bytes()).bytes(value.as_bytes());
+        }
+        self
+    }
+
+    /// SHA-256 (十六进制).
+    pub fn finish(self) -> String {
+        self.0.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// 磁盘缓存索引.
+struct Store {
+    dir: PathBuf,
+
+    max_bytes: u64,
+
+    /// 键 → (大小, 访问序号).
+    entries: HashMap<String, (u64, u64)>,
+
+    /// 访问序号 → 键, 序号最小的为最近最少使用.
+    order: BTreeMap<u64, String>,
+
+    tick: u64,
+
+    bytes: u64,
+}
+
+impl Store {
+    /// 打开缓存目录, 按照文件修改时间恢复访问顺序, 删除未完成的临时文件.
+    fn open(dir: PathBuf, max_bytes: u64) -> Result<Store, MediaError> {
+        fs::create_dir_all(&dir)?;
+        let mut files = Vec::new();
+        for shard in fs::read_dir(&dir)? {
+            let shard = shard?;
+            if !shard.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(shard.path())? {
+                let file = file?;
+                let name = file.file_name().to_string_lossy().to_string();
+                if name.ends_with(".tmp") {
+                    let _ = fs::remove_file(file.path());
+                    continue;
+                }
+                let metadata = file.metadata()?;
+                if metadata.is_file() && name.len() == 64 {
+                    files.push((metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH), name, metadata.len()));
+                }
+            }
+        }
+        files.sort();
+        let mut store = Store { dir, max_bytes, entries: HashMap::new(), order: BTreeMap::new(), tick: 0, bytes: 0 };
+        for (_, key, size) in files {
+            store.insert(key, size);
+        }
+        remove_files(store.evict());
+        Ok(store)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(key)
+    }
+
+    /// 更新访问顺序.
+    fn touch(&mut self, key: &str) -> bool {
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            None => false,
+            Some((_, tick)) => {
+                self.order.remove(tick);
+                *tick = self.tick;
+                self.order.insert(self.tick, key.to_string());
+                true
+            }
+        }
+    }
+
+    fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        self.tick += 1;
+        self.bytes += size;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (size, self.tick));
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, tick)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+            self.bytes -= size;
+        }
+    }
+
+    /// 超出大小时淘汰最近最少使用的结果, 返回需要删除的文件.
+    fn evict(&mut self) -> Vec<PathBuf> {
+        let mut evicted = Vec::new();
+        while self.bytes > self.max_bytes {
+            let Some((_, key)) = self.order.pop_first() else { break };
+            if let Some((size, _)) = self.entries.remove(&key) {
+                self.bytes -= size;
+            }
+            evicted.push(self.path(&key));
+        }
+        evicted
+    }
+}
+
+/// 正在处理的请求, 相同的请求等待首个请求的结果.
+#[derive(Default)]
+struct Flight {
+    /// None 处理中, Some(None) 处理失败.
+    result: Mutex<Option<Option<Arc<Vec<u8>>>>>,
+
+    done: Condvar,
+}
+
+/// 首个请求结束 (包括出错、panic) 时唤醒等待的请求.
+struct FlightGuard<'a> {
+    key: &'a str,
+    flight: &'a Flight,
+    data: Option<Arc<Vec<u8>>>,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        lock(&FLIGHTS).remove(self.key);
+        *lock(&self.flight.result) = Some(self.data.take());
+        self.flight.done.notify_all();
+    }
+}
+
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+
+static FLIGHTS: Mutex<BTreeMap<String, Arc<Flight>>> = Mutex::new(BTreeMap::new());
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+static COALESCED: AtomicU64 = AtomicU64::new(0);
+
+static EVICTIONS: AtomicU64 = AtomicU64::new(0);
+
+static TEMP_ID: AtomicU64 = AtomicU64::new(0);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// 替换缓存配置, 打开目录时恢复已有结果.
+pub fn configure(config: CacheConfig) -> Result<(), MediaError> {
+    let store = match config.dir {
+        None => None,
+        Some(dir) => Some(Store::open(dir, config.max_bytes)?),
+    };
+    *lock(&STORE) = store;
+    Ok(())
+}
+
+/// 是否开启缓存.
+pub fn enabled() -> bool {
+    lock(&STORE).is_some()
+}
+
+/// 缓存统计.
+pub fn stats() -> CacheStats {
+    let store = lock(&STORE);
+    CacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        coalesced: COALESCED.load(Ordering::Relaxed),
+        evictions: EVICTIONS.load(Ordering::Relaxed),
+        entries: store.as_ref().map(|store| store.entries.len() as u64).unwrap_or(0),
+        bytes: store.as_ref().map(|store| store.bytes).unwrap_or(0),
+    }
+}
+
+/// 读取缓存结果, 未命中时处理并写入缓存; 相同的请求同时到达时只处理一次, 其他请求等待结果.
+///
+/// 未开启缓存时直接处理, 不计算缓存键; 处理失败不缓存, 等待的请求各自重新处理.
+pub fn cached<K, F>(key: K, compute: F) -> Result<Vec<u8>, MediaError>
+    where K: FnOnce() -> CacheKey, F: FnOnce() -> Result<Vec<u8>, MediaError> {
+    if !enabled() {
+        return compute();
+    }
+    let key = key().finish();
+    if let Some(data) = read(&key) {
+        HITS.fetch_add(1, Ordering::Relaxed);
+        return Ok(data);
+    }
+
+    let (flight, leader) = {
+        let mut flights = lock(&FLIGHTS);
+        match flights.get(&key) {
+            Some(flight) => (flight.clone(), false),
+            None => {
+                let flight = Arc::new(Flight::default());
+                flights.insert(key.clone(), flight.clone());
+                (flight, true)
+            }
+        }
+    };
+    if !leader {
+        let mut result = lock(&flight.result);
+        while result.is_none() {
+            result = flight.done.wait(result).unwrap_or_else(|error| error.into_inner());
+        }
+        if let Some(Some(data)) = result.as_ref() {
+            COALESCED.fetch_add(1, Ordering::Relaxed);
+            return Ok(data.as_ref().clone());
+        }
+        drop(result);
+        MISSES.fetch_add(1, Ordering::Relaxed);
+        return compute();
+    }
+
+    let mut guard = FlightGuard { key: &key, flight: &flight, data: None };
+    // 查找缓存后、开始处理前其他请求可能已经写入
+    if let Some(data) = read(&key) {
+        HITS.fetch_add(1, Ordering::Relaxed);
+        guard.data = Some(Arc::new(data.clone()));
+        return Ok(data);
+    }
+    MISSES.fetch_add(1, Ordering::Relaxed);
+    let data = compute()?;
+    write(&key, &data);
+    guard.data = Some(Arc::new(data.clone()));
+    Ok(data)
+}
+
+fn read(key: &str) -> Option<Vec<u8>> {
+    let path = {
+        let mut store = lock(&STORE);
+        let store = store.as_mut()?;
+        if !store.touch(key) {
+            return None;
+        }
+        store.path(key)
+    };
+    match fs::read(&path) {
+        Ok(data) => {
+            // 修改时间作为访问时间, 重新打开目录时恢复访问顺序
+            let _ = fs::File::options().write(true).open(&path).and_then(|file| file.set_modified(SystemTime::now()));
+            Some(data)
+        }
+        Err(_) => {
+            if let Some(store) = lock(&STORE).as_mut() {
+                store.remove(key);
+            }
+            None
+        }
+    }
+}
+
+/// 写入缓存, 先写入临时文件再重命名, 写入失败时忽略.
+fn write(key: &str, data: &[u8]) {
+    let path = match lock(&STORE).as_ref() {
+        Some(store) if (data.len() as u64) <= store.max_bytes => store.path(key),
+        _ => return,
+    };
+    let temp = path.with_file_name(format!("{}.{}.{}.tmp", key, std::process::id(), TEMP_ID.fetch_add(1, Ordering::Relaxed)));
+    let written = path.parent().map(fs::create_dir_all).unwrap_or(Ok(()))
+        .and_then(|_| fs::write(&temp, data))
+        .and_then(|_| fs::rename(&temp, &path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+        return;
+    }
+    let evicted = match lock(&STORE).as_mut() {
+        Some(store) => {
+            store.insert(key.to_string(), data.len() as u64);
+            store.evict()
+        }
+        None => vec![],
+    };
+    remove_files(evicted);
+}
+
+fn remove_files(paths: Vec<PathBuf>) {
+    EVICTIONS.fetch_add(paths.len() as u64, Ordering::Relaxed);
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_ignores_param_order_and_skipped() {
+        let a = HashMap::from([("args".to_string(), "ZW100".to_string()), ("type".to_string(), "webp".to_string())]);
+        let mut b = a.clone();
+        b.insert("to".to_string(), "/tmp/a.webp".to_string());
+        let key = |map| CacheKey::new("edit").params(map, &["to"]).bytes(b"image").finish();
+        assert_eq!(key(&a), key(&b));
+        assert_ne!(key(&a), CacheKey::new("edit").params(&a, &[]).bytes(b"image2").finish());
+        assert_ne!(CacheKey::new("a").bytes(b"bc").finish(), CacheKey::new("a").bytes(b"b").bytes(b"c").finish());
+    }
+
+    #[test]
+    fn store_evicts_least_recently_used() {
+        let dir = std::env::temp_dir().join(format!("rmedia-cache-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut store = Store::open(dir.clone(), 10).unwrap();
+        store.insert("aa01".to_string(), 4);
+        store.insert("bb02".to_string(), 4);
+        assert!(store.evict().is_empty());
+        store.touch("aa01");
+        store.insert("cc03".to_string(), 4);
+        assert_eq!(store.evict(), vec![dir.join("bb").join("bb02")]);
+        assert!(store.entries.contains_key("aa01") && store.entries.contains_key("cc03"));
+        assert_eq!(store.bytes, 8);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
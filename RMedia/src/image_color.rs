@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, ImageBuffer, ImageFormat};
+use img_parts::{Bytes, DynImage, ImageICC};
+use lcms2::{CIExyY, CIExyYTRIPLE, ColorSpaceSignature, Flags, Intent, Locale, MLU, PixelFormat, Profile, TagSignature,
+            ToneCurve, Transform};
+
+use crate::error::MediaError;
+use crate::image_strip::Metadata;
+use crate::{image_decode, image_decode_cache, image_limits, image_metadata, image_sandbox, image_style};
+
+/// 输出色彩空间.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetProfile {
+    Srgb,
+    DisplayP3,
+    AdobeRgb,
+
+    /// ICC 配置文件路径.
+    File(String),
+}
+
+impl TargetProfile {
+    /// 解析名称: srgb、display-p3、adobe-rgb, file: 前缀表示 ICC 文件路径, 其他名称报错.
+    pub fn parse(value: &str) -> Result<TargetProfile, MediaError> {
+        if let Some(path) = value.trim().strip_prefix("file:") {
+            return match path.is_empty() {
+                true => Err(MediaError::InvalidParam("icc file path not found".to_string())),
+                false => Ok(TargetProfile::File(path.to_string())),
+            };
+        }
+        match value.trim().to_ascii_lowercase().as_str() {
+            "srgb" => Ok(TargetProfile::Srgb),
+            "display-p3" | "p3" => Ok(TargetProfile::DisplayP3),
+            "adobe-rgb" | "adobergb" => Ok(TargetProfile::AdobeRgb),
+            _ => Err(MediaError::InvalidParam(format!("unknown icc profile `{}`", value))),
+        }
+    }
+
+    /// 创建 lcms 配置文件.
+    fn profile(&self) -> Result<Profile, MediaError> {
+        // D65 白点
+        let white = CIExyY { x: 0.3127, y: 0.3290, Y: 1.0 };
+        let primaries = |red: (f64, f64), green: (f64, f64), blue: (f64, f64)| CIExyYTRIPLE {
+            Red: CIExyY { x: red.0, y: red.1, Y: 1.0 },
+            Green: CIExyY { x: green.0, y: green.1, Y: 1.0 },
+            Blue: CIExyY { x: blue.0, y: blue.1, Y: 1.0 },
+        };
+        let (mut profile, description) = match self {
+            TargetProfile::Srgb => return Ok(Profile::new_srgb()),
+            TargetProfile::File(path) => return Profile::new_icc(&image_sandbox::read_file(path)?).map_err(color_error),
+            TargetProfile::DisplayP3 => {
+                // sRGB 传输曲线
+                let curve = ToneCurve::new_parametric(4, &[2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045]).map_err(color_error)?;
+                let primaries = primaries((0.680, 0.320), (0.265, 0.690), (0.150, 0.060));
+                (Profile::new_rgb(&white, &primaries, &[&curve, &curve, &curve]).map_err(color_error)?, "Display P3")
+            }
+            TargetProfile::AdobeRgb => {
+                let curve = ToneCurve::new(2.19921875);
+                let primaries = primaries((0.640, 0.330), (0.210, 0.710), (0.150, 0.060));
+                (Profile::new_rgb(&white, &primaries, &[&curve, &curve, &curve]).map_err(color_error)?, "Adobe RGB (1998)")
+            }
+        };
+        let mut mlu = MLU::new(1);
+        mlu.set_text_ascii(description, Locale::none());
+        profile.write_tag(TagSignature::ProfileDescriptionTag, lcms2::Tag::MLU(&mlu));
+        Ok(profile)
+    }
+}
+
+/// 色彩管理选项.
+#[derive(Debug, Clone)]
+pub struct ColorOptions {
+    /// 输出色彩空间, None 表示不转换.
+    pub target: Option<TargetProfile>,
+
+    /// 在输出图片中写入输出色彩空间的 ICC 配置文件.
+    pub embed: bool,
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        ColorOptions { target: Some(TargetProfile::Srgb), embed: false }
+    }
+}
+
+impl ColorOptions {
+    /// 读取参数 icc (srgb、display-p3、adobe-rgb、none 或 file:ICC 文件路径, 默认 srgb)
+    /// 和 icc_embed (默认只有非 sRGB 输出时写入).
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<ColorOptions, MediaError> {
+        let target = match query_params_map.get("icc") {
+            None => Some(TargetProfile::Srgb),
+            Some(value) if value.eq_ignore_ascii_case("none") => None,
+            Some(value) => Some(TargetProfile::parse(value)?),
+        };
+        let embed_default = !matches!(target, None | Some(TargetProfile::Srgb));
+        let embed = image_style::query_bool(query_params_map, "icc_embed", embed_default)?;
+        Ok(ColorOptions { target, embed })
+    }
+
+    /// 缓存键中的输出色彩空间: ICC 文件按照沙箱读取后计算内容的 SHA-256, 其他色彩空间由参数本身区分, 返回 None.
+    pub fn profile_key(&self) -> Result<Option<String>, MediaError> {
+        match &self.target {
+            Some(TargetProfile::File(path)) => Ok(Some(image_decode_cache::bytes_key(&image_sandbox::read_file(path)?))),
+            _ => Ok(None),
+        }
+    }
+
+    /// 转换色彩空间后源图片的配置文件不再适用, 替换为输出色彩空间的配置文件 (icc_embed) 或删除.
+    pub fn update_metadata(&self, metadata: &mut Metadata) -> Result<(), MediaError> {
+        metadata.icc = match &self.target {
+            None => return Ok(()),
+            Some(target) if self.embed => Some(target.profile()?.icc().map_err(color_error)?),
+            Some(_) => None,
+        };
+        Ok(())
+    }
+
+    /// 解码图片并转换到输出色彩空间, CMYK JPEG 按照内嵌配置文件转换为 RGB.
+    pub fn decode<P: AsRef<Path>>(&self, buffer: &[u8], path: Option<P>, auto_orient: bool) -> Result<DynamicImage, MediaError> {
+        let source = icc_profile(buffer);
+        if let Some(image) = self.decode_cmyk(buffer, source.as_deref(), auto_orient)? {
+            return Ok(image);
+        }
+        let image = image_decode::decode(buffer, path, auto_orient)?;
+        self.convert(image, source.as_deref())
+    }
+
+    /// 按照内嵌的 CMYK 配置文件解码 CMYK JPEG, 其他图片返回 None.
+    pub fn decode_cmyk(&self, buffer: &[u8], source: Option<&[u8]>, auto_orient: bool) -> Result<Option<DynamicImage>, MediaError> {
+        let (target, source) = match (&self.target, source) {
+            (Some(target), Some(source)) => (target, source),
+            _ => return Ok(None),
+        };
+        Ok(decode_cmyk(buffer, source, target)?.map(|image| match image_decode::orientation(buffer) {
+            Some(value) if auto_orient => image_decode::orient(image, value),
+            _ => image,
+        }))
+    }
+
+    /// 将图片从源配置文件 (没有时视为 sRGB) 转换到输出色彩空间, 只处理 RGB 配置文件.
+    pub fn convert(&self, image: DynamicImage, source: Option<&[u8]>) -> Result<DynamicImage, MediaError> {
+        let target = match &self.target {
+            None => return Ok(image),
+            Some(value) => value,
+        };
+        let source_profile = match source {
+            Some(value) => match Profile::new_icc(value) {
+                Ok(profile) if profile.color_space() == ColorSpaceSignature::RgbData => profile,
+                // 无法解析或非 RGB 配置文件保持原样
+                _ => return Ok(image),
+            },
+            None => Profile::new_srgb(),
+        };
+        if same_profile(source, target) {
+            return Ok(image);
+        }
+        let target_profile = target.profile()?;
+        // 按照字节处理像素, 16 位图片保持位深
+        let transform = |format| -> Result<Transform<u8, u8>, MediaError> {
+            Transform::new_flags(&source_profile, format, &target_profile, format, Intent::Perceptual, Flags::COPY_ALPHA)
+                .map_err(color_error)
+        };
+        Ok(match image {
+            DynamicImage::ImageRgb16(mut buffer) => {
+                transform(PixelFormat::RGB_16)?.transform_in_place(bytemuck::cast_slice_mut(&mut buffer));
+                DynamicImage::ImageRgb16(buffer)
+            }
+            DynamicImage::ImageRgba16(mut buffer) => {
+                transform(PixelFormat::RGBA_16)?.transform_in_place(bytemuck::cast_slice_mut(&mut buffer));
+                DynamicImage::ImageRgba16(buffer)
+            }
+            image if image.color().has_alpha() => {
+                let mut buffer = image.to_rgba8();
+                transform(PixelFormat::RGBA_8)?.transform_in_place(&mut buffer);
+                DynamicImage::ImageRgba8(buffer)
+            }
+            image => {
+                let mut buffer = image.to_rgb8();
+                transform(PixelFormat::RGB_8)?.transform_in_place(&mut buffer);
+                DynamicImage::ImageRgb8(buffer)
+            }
+        })
+    }
+}
+
+/// 读取源图片 (JPEG、PNG、WebP) 内嵌的 ICC 配置文件.
+pub fn icc_profile(buffer: &[u8]) -> Option<Vec<u8>> {
+    match DynImage::from_bytes(Bytes::copy_from_slice(buffer)) {
+        Ok(Some(container)) => container.icc_profile().map(|value| value.to_vec()),
+        _ => None,
+    }
+}
+
+/// 源配置文件与输出色彩空间相同 (sRGB 按照描述判断).
+fn same_profile(source: Option<&[u8]>, target: &TargetProfile) -> bool {
+    match (source, target) {
+        (None, TargetProfile::Srgb) => true,
+        (Some(value), TargetProfile::Srgb) => image_metadata::icc_description(value)
+            .map(|description| description.starts_with("sRGB"))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// 使用 CMYK 配置文件解码 CMYK JPEG, 不是 CMYK JPEG 时返回 None.
+fn decode_cmyk(buffer: &[u8], source: &[u8], target: &TargetProfile) -> Result<Option<DynamicImage>, MediaError> {
+    if image::guess_format(buffer).ok() != Some(ImageFormat::Jpeg) {
+        return Ok(None);
+    }
+    let source_profile = match Profile::new_icc(source) {
+        Ok(profile) if profile.color_space() == ColorSpaceSignature::CmykData => profile,
+        _ => return Ok(None),
+    };
+    // 解码前按照全局限制检查输入大小和图片宽高
+    let limits = image_limits::limits();
+    limits.check_bytes(buffer.len() as u64)?;
+    let decode_error = |error: jpeg_decoder::Error| MediaError::DecodeError(format!("jpeg decode fail: {}", error));
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(buffer));
+    decoder.read_info().map_err(decode_error)?;
+    let info = decoder.info().ok_or(MediaError::DecodeError("jpeg info not found".to_string()))?;
+    if info.pixel_format != jpeg_decoder::PixelFormat::CMYK32 {
+        return Ok(None);
+    }
+    limits.check_dimensions(info.width as u32, info.height as u32)?;
+    decoder.set_max_decoding_buffer_size(usize::try_from(limits.max_alloc).unwrap_or(usize::MAX));
+    let pixels = decoder.decode().map_err(decode_error)?;
+    let transform: Transform<u8, u8> = Transform::new(&source_profile, PixelFormat::CMYK_8, &target.profile()?,
+                                                      PixelFormat::RGB_8, Intent::Perceptual).map_err(color_error)?;
+    let mut rgb = vec![0u8; info.width as usize * info.height as usize * 3];
+    transform.transform_pixels(&pixels, &mut rgb);
+    let image = ImageBuffer::from_raw(info.width as u32, info.height as u32, rgb)
+        .ok_or(MediaError::DecodeError("jpeg size mismatch".to_string()))?;
+    Ok(Some(DynamicImage::ImageRgb8(image)))
+}
+
+fn color_error(error: lcms2::Error) -> MediaError {
+    MediaError::Error(format!("color convert fail: {}", error))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_target_profile() {
+        assert_eq!(TargetProfile::parse("Display-P3").unwrap(), TargetProfile::DisplayP3);
+        assert_eq!(TargetProfile::parse("file:/icc/cmyk.icc").unwrap(), TargetProfile::File("/icc/cmyk.icc".to_string()));
+        assert!(matches!(TargetProfile::parse("srbg"), Err(MediaError::InvalidParam(_))));
+        assert!(matches!(TargetProfile::parse("file:"), Err(MediaError::InvalidParam(_))));
+    }
+
+    #[test]
+    fn profile_key_follows_file_contents() {
+        let path = std::env::temp_dir().join(format!("rmedia-profile-test-{}.icc", std::process::id()));
+        let options = ColorOptions { target: Some(TargetProfile::File(path.display().to_string())), embed: false };
+        std::fs::write(&path, b"first").unwrap();
+        let first = options.profile_key().unwrap();
+        std::fs::write(&path, b"second").unwrap();
+        assert!(first.is_some() && first != options.profile_key().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(ColorOptions::default().profile_key().unwrap(), None);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat};
+use image::io::Reader;
+
+use crate::error::MediaError;
+use crate::image_limits;
+use crate::image_sandbox;
+
+/// 读取 EXIF 方向 (1-8), 没有 EXIF 或读取失败返回 None.
+pub fn orientation(buffer: &[u8]) -> Option<u32> {
+    let exif = exif::Reader::new().read_from_container(&mut Cursor::new(buffer)).ok()?;
+    let field = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?;
+    match field.value.get_uint(0)? {
+        value @ 1..=8 => Some(value),
+        _ => None,
+    }
+}
+
+/// 按照 EXIF 方向矫正图片.
+pub fn orient(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// 矫正后的宽高 (方向 5-8 宽高互换).
+pub fn oriented_dimensions(width: u32, height: u32, orientation: Option<u32>) -> (u32, u32) {
+    match orientation {
+        Some(5..=8) => (height, width),
+        _ => (width, height),
+    }
+}
+
+/// 从内存解码图片, auto_orient 为 true 时按照 EXIF 方向矫正.
+pub fn load_from_memory(buffer: &[u8], auto_orient: bool) -> Result<DynamicImage, MediaError> {
+    decode(buffer, None::<&Path>, auto_orient)
+}
+
+/// 从文件解码原始图片 (不矫正) 并读取 EXIF 方向.
+pub fn open_with_orientation<P: AsRef<Path>>(path: P) -> Result<(DynamicImage, Option<u32>), MediaError> {
+    decode_with_orientation(&image_sandbox::read_file(&path)?, Some(path))
+}
+
+/// 解码图片, auto_orient 为 true 时按照 EXIF 方向矫正, path 为文件来源 (用于识别格式).
+pub fn decode<P: AsRef<Path>>(buffer: &[u8], path: Option<P>, auto_orient: bool) -> Result<DynamicImage, MediaError> {
+    let (image, orientation) = decode_with_orientation(buffer, path)?;
+    Ok(match orientation {
+        Some(value) if auto_orient => orient(image, value),
+        _ => image,
+    })
+}
+
+/// 解码原始图片 (不矫正) 并读取 EXIF 方向, 无法从内容识别格式时按照扩展名识别.
+/// 解码前按照全局限制检查输入大小和图片宽高.
+pub fn decode_with_orientation<P: AsRef<Path>>(buffer: &[u8], path: Option<P>) -> Result<(DynamicImage, Option<u32>), MediaError> {
+    let limits = image_limits::limits();
+    limits.check_bytes(buffer.len() as u64)?;
+    let (width, height) = reader(buffer, path.as_ref())?.into_dimensions()?;
+    limits.check_dimensions(width, height)?;
+    let mut reader = reader(buffer, path.as_ref())?;
+    reader.limits(limits.image_limits());
+    Ok((reader.decode()?, orientation(buffer)))
+}
+
+/// 创建解码器, 无法从内容识别格式时按照扩展名识别.
+fn reader<P: AsRef<Path>>(buffer: &[u8], path: Option<P>) -> Result<Reader<Cursor<&[u8]>>, MediaError> {
+    let mut reader = Reader::new(Cursor::new(buffer)).with_guessed_format()?;
+    if let (None, Some(path)) = (reader.format(), path) {
+        reader.set_format(ImageFormat::from_path(path)?);
+    }
+    Ok(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, ImageOutputFormat, Rgba, RgbaImage};
+
+    use crate::image_encode::OutputFormat;
+    use crate::image_strip::{self, Metadata};
+
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    /// 3x2 的图片, 只有左上角为红色.
+    fn marked() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(3, 2, |x, y| if (x, y) == (0, 0) { RED } else { Rgba([0, 0, 0, 255]) }))
+    }
+
+    fn red_corner(image: &DynamicImage) -> (u32, u32) {
+        let image = image.to_rgba8();
+        image.enumerate_pixels().find(|(_, _, pixel)| **pixel == RED).map(|(x, y, _)| (x, y)).unwrap()
+    }
+
+    /// 写入 EXIF 方向的 PNG.
+    fn png_with_orientation(orientation: u16) -> Vec<u8> {
+        let mut png = Cursor::new(Vec::new());
+        marked().write_to(&mut png, ImageOutputFormat::Png).unwrap();
+        // TIFF 小端, 一个 IFD 只有 Orientation (SHORT)
+        let mut exif = b"II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0".to_vec();
+        exif.extend_from_slice(&orientation.to_le_bytes());
+        exif.extend_from_slice(&[0; 6]);
+        let metadata = Metadata { exif: Some(exif), ..Metadata::default() };
+        image_strip::embed(png.into_inner(), OutputFormat::Png, &metadata).unwrap()
+    }
+
+    #[test]
+    fn orient_all_orientations() {
+        let expected = [
+            (1, (3, 2), (0, 0)),
+            (2, (3, 2), (2, 0)),
+            (3, (3, 2), (2, 1)),
+            (4, (3, 2), (0, 1)),
+            (5, (2, 3), (0, 0)),
+            (6, (2, 3), (1, 0)),
+            (7, (2, 3), (1, 2)),
+            (8, (2, 3), (0, 2)),
+        ];
+        for (orientation, dimensions, corner) in expected {
+            let image = orient(marked(), orientation);
+            assert_eq!(image.dimensions(), dimensions, "orientation {}", orientation);
+            assert_eq!(red_corner(&image), corner, "orientation {}", orientation);
+            assert_eq!(oriented_dimensions(3, 2, Some(orientation)), dimensions);
+        }
+    }
+
+    #[test]
+    fn decode_with_exif_orientation() {
+        let png = png_with_orientation(6);
+        assert_eq!(orientation(&png), Some(6));
+
+        let image = load_from_memory(&png, true).unwrap();
+        assert_eq!((image.dimensions(), red_corner(&image)), ((2, 3), (1, 0)));
+
+        // auto_orient=false 保持原始方向
+        let image = load_from_memory(&png, false).unwrap();
+        assert_eq!((image.dimensions(), red_corner(&image)), ((3, 2), (0, 0)));
+
+        assert_eq!(orientation(&png_with_orientation(9)), None);
+    }
+}
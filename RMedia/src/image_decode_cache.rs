@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::UNIX_EPOCH;
+
+use image::DynamicImage;
+use sha2::{Digest, Sha256};
+
+use crate::error::MediaError;
+use crate::image_style;
+
+/// 默认解码缓存大小 256MB (按照像素内存计算).
+const DEFAULT_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// 解码缓存配置, 同一张源图片生成多个样式时只解码一次.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeCacheConfig {
+    /// 全局开启, 单次调用可以通过参数 decode_cache 覆盖.
+    pub enabled: bool,
+
+    /// 已解码图片的最大像素内存 (字节), 超出时淘汰最近最少使用的图片.
+    pub max_bytes: u64,
+}
+
+impl DecodeCacheConfig {
+    /// 默认关闭.
+    pub const DEFAULT: DecodeCacheConfig = DecodeCacheConfig { enabled: false, max_bytes: DEFAULT_MAX_BYTES };
+
+    /// 读取参数 enabled (默认 true)、max_bytes (默认 256MB).
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<DecodeCacheConfig, MediaError> {
+        Ok(DecodeCacheConfig {
+            enabled: image_style::query_bool(query_params_map, "enabled", true)?,
+            max_bytes: match query_params_map.get("max_bytes") {
+                None => DEFAULT_MAX_BYTES,
+                Some(value) => value.parse::<u64>()?,
+            },
+        })
+    }
+}
+
+/// 已解码图片, 按照访问顺序淘汰.
+struct Entries {
+    /// 键 → (图片, 访问序号).
+    images: BTreeMap<String, (Arc<DynamicImage>, u64)>,
+
+    /// 访问序号 → 键, 序号最小的为最近最少使用.
+    order: BTreeMap<u64, String>,
+
+    tick: u64,
+
+    bytes: u64,
+}
+
+impl Entries {
+    const EMPTY: Entries = Entries { images: BTreeMap::new(), order: BTreeMap::new(), tick: 0, bytes: 0 };
+
+    fn get(&mut self, key: &str) -> Option<Arc<DynamicImage>> {
+        self.tick += 1;
+        let (image, tick) = self.images.get_mut(key)?;
+        self.order.remove(tick);
+        *tick = self.tick;
+        self.order.insert(self.tick, key.to_string());
+        Some(image.clone())
+    }
+
+    fn insert(&mut self, key: String, image: Arc<DynamicImage>, max_bytes: u64) {
+        if let Some((image, tick)) = self.images.remove(&key) {
+            self.order.remove(&tick);
+            self.bytes -= size(&image);
+        }
+        self.tick += 1;
+        self.bytes += size(&image);
+        self.order.insert(self.tick, key.clone());
+        self.images.insert(key, (image, self.tick));
+        self.evict(max_bytes);
+    }
+
+    fn evict(&mut self, max_bytes: u64) {
+        while self.bytes > max_bytes {
+            let Some((_, key)) = self.order.pop_first() else { break };
+            if let Some((image, _)) = self.images.remove(&key) {
+                self.bytes -= size(&image);
+            }
+        }
+    }
+}
+
+static CONFIG: RwLock<DecodeCacheConfig> = RwLock::new(DecodeCacheConfig::DEFAULT);
+
+static ENTRIES: Mutex<Entries> = Mutex::new(Entries::EMPTY);
+
+fn lock() -> MutexGuard<'static, Entries> {
+    ENTRIES.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+fn size(image: &DynamicImage) -> u64 {
+    image.as_bytes().len() as u64
+}
+
+/// 替换全局解码缓存配置, 超出新的大小时立即淘汰.
+pub fn set_config(config: DecodeCacheConfig) {
+    *CONFIG.write().unwrap_or_else(|error| error.into_inner()) = config;
+    let mut entries = lock();
+    entries.evict(if config.enabled { config.max_bytes } else { 0 });
+}
+
+/// 当前全局解码缓存配置.
+pub fn config() -> DecodeCacheConfig {
+    *CONFIG.read().unwrap_or_else(|error| error.into_inner())
+}
+
+/// 文件来源的键: 真实路径、大小和修改时间, metadata 为读取前从同一个文件句柄获取的元数据.
+/// 读取到的长度与元数据不一致 (读取期间文件被修改) 或无法获取修改时间时返回 None (按照内容计算).
+pub fn file_key(path: &Path, metadata: &Metadata, length: usize) -> Option<String> {
+    if metadata.len() != length as u64 {
+        return None;
+    }
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("file:{}:{}:{}", path.display(), metadata.len(), modified.as_nanos()))
+}
+
+/// 内存来源的键: 内容的 SHA-256.
+pub fn bytes_key(buffer: &[u8]) -> String {
+    let hash = Sha256::digest(buffer);
+    format!("sha256:{}", hash.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+}
+
+/// 读取已解码的图片, 未命中时解码并缓存; enabled 为 false 时直接解码.
+///
+/// key 为来源键加上影响解码结果的参数, 只在开启时计算; 超出缓存大小的图片不缓存.
+pub fn decoded<K, F>(enabled: bool, key: K, decode: F) -> Result<DynamicImage, MediaError>
+    where K: FnOnce() -> String, F: FnOnce() -> Result<DynamicImage, MediaError> {
+    let max_bytes = config().max_bytes;
+    if !enabled || max_bytes == 0 {
+        return decode();
+    }
+    let key = key();
+    if let Some(image) = lock().get(&key) {
+        return Ok(image.as_ref().clone());
+    }
+    let image = decode()?;
+    if size(&image) <= max_bytes {
+        lock().insert(key, Arc::new(image.clone()), max_bytes);
+    }
+    Ok(image)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_evict_least_recently_used() {
+        let image = || Arc::new(DynamicImage::new_rgba8(2, 2));
+        let mut entries = Entries::EMPTY;
+        entries.insert("a".to_string(), image(), 40);
+        entries.insert("b".to_string(), image(), 40);
+        assert!(entries.get("a").is_some());
+        entries.insert("c".to_string(), image(), 40);
+        assert!(entries.get("b").is_none());
+        assert!(entries.get("a").is_some() && entries.get("c").is_some());
+        assert_eq!(entries.bytes, 32);
+    }
+
+    #[test]
+    fn file_key_requires_matching_length() {
+        let path = std::env::temp_dir().join(format!("rmedia-decode-cache-test-{}", std::process::id()));
+        std::fs::write(&path, b"image").unwrap();
+        let (buffer, canonical, metadata) = crate::image_sandbox::read_file_with_metadata(&path).unwrap();
+        assert!(file_key(&canonical, &metadata, buffer.len()).is_some());
+        assert!(file_key(&canonical, &metadata, buffer.len() + 1).is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, GenericImageView, ImageOutputFormat, Rgba};
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::gif::GifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+
+use crate::error::MediaError;
+use crate::image_strip;
+use crate::image_strip::{Metadata, StripPolicy};
+use crate::image_style;
+use crate::image_webp;
+use crate::image_webp::WebPOptions;
+
+/// 输出图片格式.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Avif,
+    Bmp,
+    Tiff,
+    WebP,
+}
+
+impl OutputFormat {
+    /// 解析格式名称 (不区分大小写).
+    pub fn parse(value: &str) -> Result<OutputFormat, MediaError> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "jpeg" | "jpg" => Ok(OutputFormat::Jpeg),
+            "gif" => Ok(OutputFormat::Gif),
+            "avif" => Ok(OutputFormat::Avif),
+            "bmp" => Ok(OutputFormat::Bmp),
+            "tiff" | "tif" => Ok(OutputFormat::Tiff),
+            "webp" => Ok(OutputFormat::WebP),
+            _ => Err(MediaError::UnsupportedFormat(value.to_string())),
+        }
+    }
+
+    /// 根据文件扩展名识别格式.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<OutputFormat, MediaError> {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|value| value.to_str())
+            .ok_or_else(|| MediaError::UnsupportedFormat(path.display().to_string()))?;
+        OutputFormat::parse(extension)
+    }
+
+    /// 文件扩展名.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Gif => "gif",
+            OutputFormat::Avif => "avif",
+            OutputFormat::Bmp => "bmp",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::WebP => "webp",
+        }
+    }
+
+    /// MIME 类型.
+    pub fn mime(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Gif => "image/gif",
+            OutputFormat::Avif => "image/avif",
+            OutputFormat::Bmp => "image/bmp",
+            OutputFormat::Tiff => "image/tiff",
+            OutputFormat::WebP => "image/webp",
+        }
+    }
+
+    /// 格式是否可以保存透明通道.
+    pub fn supports_alpha(self) -> bool {
+        !matches!(self, OutputFormat::Jpeg | OutputFormat::Bmp)
+    }
+}
+
+/// 编码选项.
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    /// 图片质量 0-100 (JPEG、WebP、AVIF).
+    pub quality: f32,
+
+    /// PNG 压缩级别 0-9.
+    pub compression: u8,
+
+    /// AVIF 编码速度 1-10, 越大越快.
+    pub speed: u8,
+
+    /// 目标格式不支持透明时的背景色, 背景色本身透明的部分按照白色合成.
+    pub background: Rgba<u8>,
+
+    /// WebP 编码配置.
+    pub webp: WebPOptions,
+
+    /// 目标大小 (字节), 设置后自动查找不超过该大小的最高质量.
+    pub max_bytes: Option<usize>,
+
+    /// JPEG 渐进式编码, 其他格式不支持 (strict 模式返回错误, 否则忽略).
+    pub interlace: bool,
+
+    /// 严格模式, 输出格式不支持的参数返回错误.
+    pub strict: bool,
+
+    /// 元数据保留策略.
+    pub strip: StripPolicy,
+
+    /// 写入输出图片的元数据, 通过 preserve 从源图片读取.
+    pub metadata: Metadata,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            quality: 75f32,
+            compression: 6,
+            speed: 6,
+            background: Rgba([255, 255, 255, 255]),
+            webp: WebPOptions::default(),
+            max_bytes: None,
+            interlace: false,
+            strict: false,
+            strip: StripPolicy::default(),
+            metadata: Metadata::default(),
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// 读取参数 quality、compression、speed、bg、max_bytes、interlace、strict、strip 以及 WebP 编码参数.
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<EncodeOptions, MediaError> {
+        let mut options = EncodeOptions::default();
+        if let Some(value) = query_params_map.get("quality") {
+            options.quality = value.parse::<f32>()?.clamp(0f32, 100f32);
+        }
+        if let Some(value) = query_params_map.get("compression") {
+            options.compression = value.parse::<u8>()?.min(9);
+        }
+        if let Some(value) = query_params_map.get("speed") {
+            options.speed = value.parse::<u8>()?.clamp(1, 10);
+        }
+        if let Some(value) = query_params_map.get("bg") {
+            options.background = image_style::parse_color(value)?;
+        }
+        if let Some(value) = query_params_map.get("max_bytes") {
+            options.max_bytes = Some(value.parse::<usize>()?);
+        }
+        options.interlace = image_style::query_bool(query_params_map, "interlace", false)?;
+        options.strict = image_style::query_bool(query_params_map, "strict", false)?;
+        options.strip = StripPolicy::new(query_params_map)?;
+        options.webp = WebPOptions::new(query_params_map)?;
+        Ok(options)
+    }
+
+    /// 检查输出格式是否支持元数据保留策略以及 interlace (只有 JPEG 支持, strict 模式检查).
+    pub fn check(&self, format: OutputFormat) -> Result<(), MediaError> {
+        self.strip.check(format)?;
+        if self.interlace && self.strict && format != OutputFormat::Jpeg {
+            return Err(MediaError::UnsupportedFormat(format!("interlace not supported for {}", format.extension())));
+        }
+        Ok(())
+    }
+
+    /// 按照保留策略读取源图片的元数据, oriented 表示输出图片已经按照 EXIF 方向矫正.
+    pub fn preserve(&mut self, buffer: &[u8], oriented: bool) {
+        self.metadata = image_strip::extract(buffer, self.strip, oriented);
+    }
+
+    /// 复制选项并替换质量.
+    pub fn with_quality(&self, quality: f32) -> EncodeOptions {
+        let mut options = self.clone();
+        options.quality = quality;
+        options.webp.quality = quality;
+        options
+    }
+}
+
+/// 目标大小模式的结果.
+pub struct FitResult {
+    pub data: Vec<u8>,
+
+    /// 最终使用的质量.
+    pub quality: f32,
+
+    /// 最终使用的缩放比例 (1 表示未缩小).
+    pub scale: f32,
+}
+
+/// 目标大小模式最多缩小图片的次数.
+const MAX_FIT_SHRINK: usize = 8;
+
+/// 目标大小模式每次至少缩小的比例.
+const FIT_SHRINK_SCALE: f32 = 0.8;
+
+/// 在质量 1-100 之间二分查找编码结果不超过 max_bytes 的最高质量, encode 传入质量和缩放比例返回编码结果.
+/// 只支持 WebP、JPEG、AVIF.
+///
+/// 先按照最低质量编码, 超出时按照超出的比例估算缩放比例后重试, 最多编码 MAX_FIT_SHRINK + 8 次.
+pub fn fit_size<F>(format: OutputFormat, max_bytes: usize, mut encode: F) -> Result<FitResult, MediaError>
+    where F: FnMut(f32, f32) -> Result<Vec<u8>, MediaError> {
+    if !matches!(format, OutputFormat::WebP | OutputFormat::Jpeg | OutputFormat::Avif) {
+        return Err(MediaError::UnsupportedFormat(format!("{:?} with max_bytes", format)));
+    }
+    let mut scale = 1f32;
+    for _ in 0..=MAX_FIT_SHRINK {
+        let data = encode(1f32, scale)?;
+        if data.len() > max_bytes {
+            // 大小约与像素数成正比
+            scale *= (max_bytes as f32 / data.len() as f32).sqrt().min(FIT_SHRINK_SCALE);
+            continue;
+        }
+        let mut best = FitResult { data, quality: 1f32, scale };
+        let (mut low, mut high) = (2u32, 100u32);
+        while low <= high {
+            let quality = (low + high) / 2;
+            let data = encode(quality as f32, scale)?;
+            if data.len() <= max_bytes {
+                best = FitResult { data, quality: quality as f32, scale };
+                low = quality + 1;
+            } else {
+                high = quality - 1;
+            }
+        }
+        return Ok(best);
+    }
+    Err(MediaError::InvalidParam(format!("cannot encode within {} bytes", max_bytes)))
+}
+
+/// 按照格式编码图片并写入保留的元数据.
+pub fn encode(image: DynamicImage, format: OutputFormat, options: &EncodeOptions) -> Result<Vec<u8>, MediaError> {
+    options.check(format)?;
+    let data = encode_pixels(image, format, options)?;
+    image_strip::embed(data, format, &options.metadata)
+}
+
+/// 按照格式编码图片, 目标格式不支持透明时先合成到背景色上.
+fn encode_pixels(image: DynamicImage, format: OutputFormat, options: &EncodeOptions) -> Result<Vec<u8>, MediaError> {
+    let image = if !format.supports_alpha() && image.color().has_alpha() {
+        flatten(&image, options.background)
+    } else {
+        image
+    };
+    let mut buffer = Cursor::new(Vec::new());
+    match format {
+        OutputFormat::Png => {
+            let compression = match options.compression {
+                0..=2 => CompressionType::Fast,
+                3..=6 => CompressionType::Default,
+                _ => CompressionType::Best,
+            };
+            image.write_with_encoder(PngEncoder::new_with_quality(&mut buffer, compression, FilterType::Adaptive))?;
+        }
+        OutputFormat::Jpeg => {
+            let quality = (options.quality.round() as u8).max(1);
+            if options.interlace {
+                return encode_progressive_jpeg(&image, quality);
+            }
+            JpegEncoder::new_with_quality(&mut buffer, quality).encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))?;
+        }
+        OutputFormat::Gif => {
+            let rgba = image.to_rgba8();
+            GifEncoder::new(&mut buffer).encode(rgba.as_raw(), rgba.width(), rgba.height(), image::ColorType::Rgba8)?;
+        }
+        OutputFormat::Avif => {
+            return encode_avif(&image, options);
+        }
+        OutputFormat::Bmp => {
+            let rgb = image.to_rgb8();
+            BmpEncoder::new(&mut buffer).encode(rgb.as_raw(), rgb.width(), rgb.height(), image::ColorType::Rgb8)?;
+        }
+        OutputFormat::Tiff => {
+            image.write_to(&mut buffer, ImageOutputFormat::Tiff)?;
+        }
+        OutputFormat::WebP => {
+            return Ok(image_webp::encoded_webp_with(image, &options.webp)?.to_vec());
+        }
+    }
+    Ok(buffer.into_inner())
+}
+
+/// JPEG 渐进式编码.
+fn encode_progressive_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, MediaError> {
+    let rgb = image.to_rgb8();
+    let (width, height) = (u16::try_from(rgb.width()), u16::try_from(rgb.height()));
+    let (Ok(width), Ok(height)) = (width, height) else {
+        return Err(MediaError::LimitExceeded(format!("jpeg {}x{} exceeds 65535x65535", rgb.width(), rgb.height())));
+    };
+    let mut buffer = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut buffer, quality);
+    encoder.set_progressive(true);
+    encoder.encode(rgb.as_raw(), width, height, jpeg_encoder::ColorType::Rgb)
+        .map_err(|error| MediaError::Error(format!("jpeg encode fail: {}", error)))?;
+    Ok(buffer)
+}
+
+/// AVIF 编码.
+fn encode_avif(image: &DynamicImage, options: &EncodeOptions) -> Result<Vec<u8>, MediaError> {
+    let pixels = image.to_rgba8().pixels()
+        .map(|pixel| ravif::RGBA8::new(pixel[0], pixel[1], pixel[2], pixel[3]))
+        .collect::<Vec<ravif::RGBA8>>();
+    let result = ravif::Encoder::new()
+        .with_quality(options.quality.max(1f32))
+        .with_alpha_quality(options.quality.max(1f32))
+        .with_speed(options.speed)
+        .encode_rgba(ravif::Img::new(&pixels[..], image.width() as usize, image.height() as usize))
+        .map_err(|error| MediaError::Error(format!("avif encode fail: {}", error)))?;
+    Ok(result.avif_file)
+}
+
+/// 将透明图片合成到背景色上, 背景色先合成到白色上 (bg=transparent 时为白色背景).
+pub fn flatten(image: &DynamicImage, background: Rgba<u8>) -> DynamicImage {
+    let background_alpha = background[3] as u32;
+    let background = [0, 1, 2].map(|channel| ((background[channel] as u32 * background_alpha + 255 * (255 - background_alpha)) / 255) as u8);
+    let mut rgb = image::RgbImage::new(image.width(), image.height());
+    for (x, y, pixel) in image.pixels() {
+        let alpha = pixel[3] as u32;
+        let blend = |channel: usize| ((pixel[channel] as u32 * alpha + background[channel] as u32 * (255 - alpha)) / 255) as u8;
+        rgb.put_pixel(x, y, image::Rgb([blend(0), blend(1), blend(2)]));
+    }
+    DynamicImage::ImageRgb8(rgb)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_rejects_unsupported_interlace() {
+        let image = DynamicImage::new_rgb8(2, 2);
+        let options = |strict| EncodeOptions { interlace: true, strict, ..EncodeOptions::default() };
+        assert!(encode(image.clone(), OutputFormat::Jpeg, &options(true)).is_ok());
+        assert!(encode(image.clone(), OutputFormat::Png, &options(false)).is_ok());
+        assert!(matches!(encode(image.clone(), OutputFormat::Png, &options(true)), Err(MediaError::UnsupportedFormat(_))));
+        assert!(matches!(encode(image, OutputFormat::Gif, &options(true)), Err(MediaError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn fit_size_bounds_encode_attempts() {
+        let mut attempts = 0;
+        // 大小与像素数成正比, 质量每增加 1 增加 1%
+        let result = fit_size(OutputFormat::Jpeg, 1000, |quality, scale| {
+            attempts += 1;
+            Ok(vec![0; (100_000f32 * scale * scale * (1f32 + quality / 100f32)) as usize])
+        }).unwrap();
+        assert!(result.data.len() <= 1000 && result.scale < 0.1, "{} {}", result.data.len(), result.scale);
+        assert!(attempts <= MAX_FIT_SHRINK + 8, "{}", attempts);
+        assert!(fit_size(OutputFormat::Jpeg, 10, |_, _| Ok(vec![0; 1000])).is_err());
+    }
+
+    #[test]
+    fn flatten_transparent_background_to_white() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, Rgba([10, 20, 30, 0])));
+        assert_eq!(flatten(&image, Rgba([0, 0, 0, 0])).to_rgb8().get_pixel(0, 0).0, [255, 255, 255]);
+        assert_eq!(flatten(&image, Rgba([0, 0, 255, 255])).to_rgb8().get_pixel(0, 0).0, [0, 0, 255]);
+        let opaque = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, Rgba([10, 20, 30, 255])));
+        assert_eq!(flatten(&opaque, Rgba([0, 0, 0, 0])).to_rgb8().get_pixel(0, 0).0, [10, 20, 30]);
+    }
+}
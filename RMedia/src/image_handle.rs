@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use image::{DynamicImage, GenericImageView, ImageFormat};
+
+use crate::error::MediaError;
+use crate::image_animation::Animation;
+use crate::image_color::ColorOptions;
+use crate::image_encode::{EncodeOptions, OutputFormat};
+use crate::image_strip::{Metadata, StripPolicy};
+use crate::image_style::{EditOptions, EditResult};
+use crate::image_style_args::StyleArg;
+use crate::{image_animation, image_color, image_decode, image_metadata, image_style, image_style_args};
+
+/// 已解码的图片, 多次处理、编码时不再重复解码; 创建后不可修改, 样式处理返回新的图片.
+pub struct ImageHandle {
+    /// 图片 (动图为第一帧).
+    image: DynamicImage,
+
+    /// 动图所有帧, 输出 WebP、GIF 时使用.
+    animation: Option<Animation>,
+
+    /// 源图片元信息 (JSON).
+    metadata: Arc<str>,
+
+    /// 写入输出图片的元数据 (打开时按照 strip、icc 参数确定).
+    preserved: Arc<Metadata>,
+
+    /// 打开时的元数据保留策略, 编码时按照该策略检查输出格式.
+    strip: StripPolicy,
+}
+
+impl ImageHandle {
+    /// 解码图片, 读取参数 auto_orient、icc、icc_embed、strip.
+    pub fn open(buffer: &[u8], path: Option<&str>, query_params_map: &HashMap<String, String>) -> Result<ImageHandle, MediaError> {
+        let options = EditOptions::new(query_params_map)?;
+        let color = ColorOptions::new(query_params_map)?;
+        let mut encode_options = EncodeOptions::new(query_params_map)?;
+        encode_options.preserve(buffer, options.auto_orient);
+        color.update_metadata(&mut encode_options.metadata)?;
+
+        // 只解码一次: CMYK JPEG 按照配置文件解码, 动图的第一帧作为图片, 其他图片解码后转换色彩空间
+        let source = image_color::icc_profile(buffer);
+        let orient = |image: DynamicImage, orientation: Option<u32>| match orientation {
+            Some(value) if options.auto_orient => image_decode::orient(image, value),
+            _ => image,
+        };
+        let (image, animation, metadata) = if let Some(image) = color.decode_cmyk(buffer, source.as_deref(), options.auto_orient)? {
+            (image, None, image_metadata::read(buffer, path)?)
+        } else if let Some(animation) = image_animation::decode_frames(buffer)? {
+            let orientation = image_decode::orientation(buffer);
+            let animation = animation.try_map(|image| color.convert(orient(image, orientation), source.as_deref()))?;
+            let image = animation.frames.first().map(|frame| frame.image.clone())
+                .ok_or(MediaError::DecodeError("animation has no frames".to_string()))?;
+            let metadata = image_metadata::read(buffer, path)?;
+            (image, Some(animation).filter(|animation| animation.frames.len() > 1), metadata)
+        } else {
+            let (raw, orientation) = image_decode::decode_with_orientation(buffer, path)?;
+            let format = image::guess_format(buffer).ok().or_else(|| path.and_then(|value| ImageFormat::from_path(value).ok()));
+            let metadata = image_metadata::describe(buffer, format, raw.dimensions(), raw.color(), orientation, 1);
+            (color.convert(orient(raw, orientation), source.as_deref())?, None, metadata)
+        };
+        let metadata = metadata.to_json()?;
+        Ok(ImageHandle { image, animation, metadata: metadata.into(), preserved: Arc::new(encode_options.metadata), strip: encode_options.strip })
+    }
+
+    /// 执行样式操作 (参数同 ImageStyle.handle 的 args、strict、bg、clamp、upscale), 返回新的图片.
+    pub fn style(&self, query_params_map: &HashMap<String, String>) -> Result<ImageHandle, MediaError> {
+        let options = EditOptions::new(query_params_map)?;
+        let args = match query_params_map.get("args") {
+            None => vec![],
+            Some(args) => image_style_args::parse(args, options.strict)?,
+        };
+        self.style_args(&args, &options)
+    }
+
+    /// 依次执行已解析的样式操作, 返回新的图片.
+    /// 动图只处理所有帧, 图片取处理后的第一帧.
+    pub fn style_args(&self, args: &[StyleArg], options: &EditOptions) -> Result<ImageHandle, MediaError> {
+        let (image, animation) = match &self.animation {
+            None => (image_style::apply_all(self.image.clone(), args, options)?, None),
+            Some(animation) => {
+                let animation = animation.try_map_ref(|frame| image_style::apply_all(frame.clone(), args, options))?;
+                let image = animation.frames.first().map(|frame| frame.image.clone())
+                    .ok_or(MediaError::DecodeError("animation has no frames".to_string()))?;
+                (image, Some(animation))
+            }
+        };
+        Ok(ImageHandle { image, animation, metadata: self.metadata.clone(), preserved: self.preserved.clone(), strip: self.strip })
+    }
+
+    /// 编码图片 (参数同 ImageStyle.handle 的 type、quality、max_bytes、to 等), 动图输出为 WebP、GIF 时保留所有帧.
+    /// 元数据在打开时按照 strip 确定, 编码参数不能再指定 strip.
+    pub fn encode(&self, query_params_map: &HashMap<String, String>) -> Result<EditResult, MediaError> {
+        if query_params_map.contains_key("strip") {
+            return Err(MediaError::InvalidParam("strip must be set when opening the image".to_string()));
+        }
+        let format = match query_params_map.get("type") {
+            Some(value) => OutputFormat::parse(value)?,
+            None => match query_params_map.get("to") {
+                Some(output_path) => OutputFormat::from_path(output_path)?,
+                None => OutputFormat::Png,
+            }
+        };
+        let mut encode_options = EncodeOptions::new(query_params_map)?;
+        encode_options.strip = self.strip;
+        encode_options.metadata = self.preserved.as_ref().clone();
+        let result = match (&self.animation, format) {
+            (Some(animation), OutputFormat::WebP | OutputFormat::Gif) => image_style::encode_animation(animation, format, &encode_options)?,
+            _ => image_style::encode_image(self.image.clone(), format, &encode_options)?,
+        };
+        image_style::write_result(result, query_params_map.get("to"))
+    }
+
+    /// 源图片元信息 (JSON).
+    pub fn metadata_json(&self) -> &str {
+        &self.metadata
+    }
+
+    /// 当前宽高.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+}
+
+/// 已打开的图片, Java 只持有 id, 关闭后的 id 无法再访问图片.
+static HANDLES: Mutex<BTreeMap<i64, Arc<ImageHandle>>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicI64 = AtomicI64::new(1);
+
+/// 保存图片, 返回 id.
+pub fn insert(handle: ImageHandle) -> i64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    HANDLES.lock().unwrap_or_else(|error| error.into_inner()).insert(id, Arc::new(handle));
+    id
+}
+
+/// 读取图片, 处理期间其他线程关闭不会释放图片.
+pub fn get(id: i64) -> Result<Arc<ImageHandle>, MediaError> {
+    HANDLES.lock().unwrap_or_else(|error| error.into_inner()).get(&id).cloned()
+        .ok_or(MediaError::InvalidParam(format!("image handle {} closed", id)))
+}
+
+/// 关闭图片, 重复关闭忽略.
+pub fn remove(id: i64) {
+    HANDLES.lock().unwrap_or_else(|error| error.into_inner()).remove(&id);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::gif::GifEncoder;
+    use image::{Frame, ImageOutputFormat, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn open_decodes_animation_once() {
+        let mut gif = Vec::new();
+        let mut encoder = GifEncoder::new(&mut gif);
+        for value in [0, 255] {
+            encoder.encode_frame(Frame::new(RgbaImage::from_pixel(4, 3, image::Rgba([value, 0, 0, 255])))).unwrap();
+        }
+        drop(encoder);
+        let handle = ImageHandle::open(&gif, None, &HashMap::new()).unwrap();
+        assert_eq!(handle.animation.as_ref().map(|animation| animation.frames.len()), Some(2));
+        assert_eq!(handle.image.to_rgba8(), handle.animation.as_ref().unwrap().frames[0].image.to_rgba8());
+        assert!(handle.metadata_json().contains("\"frameCount\":2"));
+
+        // 动图只处理所有帧, 图片为处理后的第一帧
+        let styled = handle.style(&HashMap::from([("args".to_string(), "ZW2".to_string())])).unwrap();
+        assert!(styled.dimensions().0 < 4);
+        assert_eq!(styled.image.to_rgba8(), styled.animation.as_ref().unwrap().frames[0].image.to_rgba8());
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(5, 2).write_to(&mut png, ImageOutputFormat::Png).unwrap();
+        let handle = ImageHandle::open(png.get_ref(), None, &HashMap::new()).unwrap();
+        assert!(handle.animation.is_none());
+        assert_eq!(handle.dimensions(), (5, 2));
+    }
+
+    #[test]
+    fn strip_policy_fixed_at_open() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(2, 2).write_to(&mut png, ImageOutputFormat::Png).unwrap();
+        let params = |pairs: &[(&str, &str)]| pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>();
+        let handle = ImageHandle::open(png.get_ref(), None, &params(&[("strip", "keep-all")])).unwrap();
+        assert!(handle.encode(&params(&[("type", "png")])).is_ok());
+        assert!(matches!(handle.encode(&params(&[("type", "bmp")])), Err(MediaError::UnsupportedFormat(_))));
+        assert!(matches!(handle.encode(&params(&[("type", "png"), ("strip", "all")])), Err(MediaError::InvalidParam(_))));
+    }
+}
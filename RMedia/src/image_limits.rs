@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::error::MediaError;
+use crate::image_style_args::MAX_ARGS;
+
+/// 资源限制, 作用于所有解码入口 (WebP、二维码 logo/poster、样式处理).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// 输入文件最大字节数.
+    pub max_input_bytes: u64,
+
+    /// 图片最大宽度.
+    pub max_width: u32,
+
+    /// 图片最大高度.
+    pub max_height: u32,
+
+    /// 图片最大像素数.
+    pub max_pixels: u64,
+
+    /// 解码最大内存分配 (字节), 动图按照所有帧计算.
+    pub max_alloc: u64,
+
+    /// 动图最大帧数.
+    pub max_frames: usize,
+
+    /// 单次处理最多的样式操作数量.
+    pub max_ops: usize,
+}
+
+impl Limits {
+    /// 默认限制.
+    pub const DEFAULT: Limits = Limits {
+        max_input_bytes: 20 * 1024 * 1024,
+        max_width: 16384,
+        max_height: 16384,
+        max_pixels: 100_000_000,
+        max_alloc: 512 * 1024 * 1024,
+        max_frames: 1000,
+        max_ops: MAX_ARGS,
+    };
+
+    /// 读取参数 max_input_bytes、max_width、max_height、max_pixels、max_alloc、max_frames、max_ops, 未填写的使用默认值.
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<Limits, MediaError> {
+        let mut limits = Limits::DEFAULT;
+        if let Some(value) = query_params_map.get("max_input_bytes") {
+            limits.max_input_bytes = value.parse::<u64>()?;
+        }
+        if let Some(value) = query_params_map.get("max_width") {
+            limits.max_width = value.parse::<u32>()?;
+        }
+        if let Some(value) = query_params_map.get("max_height") {
+            limits.max_height = value.parse::<u32>()?;
+        }
+        if let Some(value) = query_params_map.get("max_pixels") {
+            limits.max_pixels = value.parse::<u64>()?;
+        }
+        if let Some(value) = query_params_map.get("max_alloc") {
+            limits.max_alloc = value.parse::<u64>()?;
+        }
+        if let Some(value) = query_params_map.get("max_frames") {
+            limits.max_frames = value.parse::<usize>()?;
+        }
+        if let Some(value) = query_params_map.get("max_ops") {
+            limits.max_ops = value.parse::<usize>()?;
+        }
+        Ok(limits)
+    }
+
+    /// 检查输入大小.
+    pub fn check_bytes(&self, length: u64) -> Result<(), MediaError> {
+        if length > self.max_input_bytes {
+            return Err(MediaError::LimitExceeded(format!("input {} bytes exceeds {} bytes", length, self.max_input_bytes)));
+        }
+        Ok(())
+    }
+
+    /// 检查宽高、像素数以及 RGBA 解码需要的内存.
+    pub fn check_dimensions(&self, width: u32, height: u32) -> Result<(), MediaError> {
+        if width > self.max_width || height > self.max_height {
+            return Err(MediaError::LimitExceeded(format!("image {}x{} exceeds {}x{}", width, height, self.max_width, self.max_height)));
+        }
+        let pixels = width as u64 * height as u64;
+        if pixels > self.max_pixels {
+            return Err(MediaError::LimitExceeded(format!("image {} pixels exceeds {} pixels", pixels, self.max_pixels)));
+        }
+        self.check_frames(1, width, height)
+    }
+
+    /// 检查动图帧数以及所有帧 (RGBA) 需要的内存.
+    pub fn check_frames(&self, frames: usize, width: u32, height: u32) -> Result<(), MediaError> {
+        if frames > self.max_frames {
+            return Err(MediaError::LimitExceeded(format!("animation {} frames exceeds {} frames", frames, self.max_frames)));
+        }
+        let alloc = frames as u64 * width as u64 * height as u64 * 4;
+        if alloc > self.max_alloc {
+            return Err(MediaError::LimitExceeded(format!("decode {} bytes exceeds {} bytes", alloc, self.max_alloc)));
+        }
+        Ok(())
+    }
+
+    /// image 解码器限制.
+    pub fn image_limits(&self) -> image::io::Limits {
+        let mut limits = image::io::Limits::default();
+        limits.max_image_width = Some(self.max_width);
+        limits.max_image_height = Some(self.max_height);
+        limits.max_alloc = Some(self.max_alloc);
+        limits
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::DEFAULT
+    }
+}
+
+/// 全局限制, 启动时从 Java 设置一次.
+static LIMITS: RwLock<Limits> = RwLock::new(Limits::DEFAULT);
+
+/// 当前限制.
+pub fn limits() -> Limits {
+    *LIMITS.read().unwrap_or_else(|error| error.into_inner())
+}
+
+/// 替换全局限制.
+pub fn set_limits(limits: Limits) {
+    *LIMITS.write().unwrap_or_else(|error| error.into_inner()) = limits;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn small() -> Limits {
+        Limits { max_input_bytes: 100, max_width: 64, max_height: 32, max_pixels: 1024, max_alloc: 8192, max_frames: 3, max_ops: 4 }
+    }
+
+    #[test]
+    fn check_bytes() {
+        assert!(small().check_bytes(100).is_ok());
+        assert!(matches!(small().check_bytes(101), Err(MediaError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn check_dimensions() {
+        assert!(small().check_dimensions(32, 32).is_ok());
+        assert!(matches!(small().check_dimensions(65, 1), Err(MediaError::LimitExceeded(_))));
+        assert!(matches!(small().check_dimensions(1, 33), Err(MediaError::LimitExceeded(_))));
+        assert!(matches!(small().check_dimensions(64, 32), Err(MediaError::LimitExceeded(_))));
+        let limits = Limits { max_alloc: 1024, ..small() };
+        assert!(matches!(limits.check_dimensions(32, 32), Err(MediaError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn check_frames() {
+        assert!(small().check_frames(2, 32, 32).is_ok());
+        assert!(matches!(small().check_frames(4, 1, 1), Err(MediaError::LimitExceeded(_))));
+        assert!(matches!(small().check_frames(3, 32, 32), Err(MediaError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn parse_limits() {
+        let query = HashMap::from([("max_width".to_string(), "10".to_string()), ("max_frames".to_string(), "2".to_string())]);
+        let limits = Limits::new(&query).unwrap();
+        assert_eq!((limits.max_width, limits.max_frames, limits.max_height), (10, 2, Limits::DEFAULT.max_height));
+        let query = HashMap::from([("max_ops".to_string(), "x".to_string())]);
+        assert!(Limits::new(&query).is_err());
+    }
+
+    #[test]
+    fn image_decoder_limits() {
+        let limits = small().image_limits();
+        assert_eq!((limits.max_image_width, limits.max_image_height, limits.max_alloc), (Some(64), Some(32), Some(8192)));
+
+        // 超出宽度的 PNG 由 image 解码器拒绝
+        let mut png = Cursor::new(Vec::new());
+        image::DynamicImage::new_rgba8(65, 1).write_to(&mut png, image::ImageOutputFormat::Png).unwrap();
+        let mut reader = image::io::Reader::new(Cursor::new(png.into_inner())).with_guessed_format().unwrap();
+        reader.limits(limits);
+        assert!(matches!(reader.decode(), Err(image::ImageError::Limits(_))));
+    }
+}
@@ -0,0 +1,339 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{ColorType, GenericImageView, ImageDecoder, ImageFormat, ImageResult};
+use image::codecs::bmp::BmpDecoder;
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::tiff::TiffDecoder;
+use img_parts::{Bytes, DynImage, ImageICC};
+use serde::Serialize;
+use webp::BitstreamFeatures;
+
+use crate::error::MediaError;
+use crate::image_animation;
+use crate::image_decode;
+use crate::image_limits;
+
+/// 图片元信息.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageMetadata {
+    /// 图片格式 (png、jpeg、webp ...).
+    pub format: Option<String>,
+
+    /// 文件大小 (字节).
+    pub file_size: usize,
+
+    /// 按照 EXIF 方向矫正后的宽高.
+    pub width: u32,
+    pub height: u32,
+
+    /// 原始宽高.
+    pub raw_width: u32,
+    pub raw_height: u32,
+
+    pub color_type: String,
+
+    /// 每个通道的位深度.
+    pub bit_depth: u16,
+
+    pub has_alpha: bool,
+
+    /// 帧数, 静态图片为 1.
+    pub frame_count: usize,
+
+    /// 渐进式 JPEG 或交错 PNG.
+    pub progressive: bool,
+
+    /// 是否无损编码 (GIF 为调色板格式, 为 false), 无法判断时为 null.
+    pub lossless: Option<bool>,
+
+    pub dpi: Option<Dpi>,
+
+    /// ICC 配置文件描述.
+    pub icc_profile: Option<String>,
+
+    pub exif: Option<ExifMetadata>,
+}
+
+/// 分辨率 (每英寸像素).
+#[derive(Debug, Serialize)]
+pub struct Dpi {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// EXIF 信息.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifMetadata {
+    pub make: Option<String>,
+    pub model: Option<String>,
+
+    /// 拍摄时间, 没有时使用修改时间.
+    pub date_time: Option<String>,
+
+    /// 方向 1-8.
+    pub orientation: Option<u32>,
+
+    pub gps: Option<Gps>,
+}
+
+/// GPS 坐标, 南纬、西经为负数.
+#[derive(Debug, Serialize)]
+pub struct Gps {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+impl ImageMetadata {
+    /// 序列化为 JSON.
+    pub fn to_json(&self) -> Result<String, MediaError> {
+        serde_json::to_string(self).map_err(|error| MediaError::Error(format!("metadata serialize fail: {}", error)))
+    }
+}
+
+/// 读取图片元信息, path 为文件来源 (用于识别格式).
+///
+/// 常见格式只读取文件头和块结构, 不解码像素; 其他格式按照资源限制解码.
+pub fn read<P: AsRef<Path>>(buffer: &[u8], path: Option<P>) -> Result<ImageMetadata, MediaError> {
+    image_limits::limits().check_bytes(buffer.len() as u64)?;
+    let format = image::guess_format(buffer).ok()
+        .or_else(|| path.as_ref().and_then(|value| ImageFormat::from_path(value).ok()));
+    let orientation = image_decode::orientation(buffer);
+    let (dimensions, color) = match format.and_then(|format| header(buffer, format)) {
+        Some(value) => value,
+        None => {
+            let (image, _) = image_decode::decode_with_orientation(buffer, path)?;
+            (image.dimensions(), image.color())
+        }
+    };
+    Ok(describe(buffer, format, dimensions, color, orientation, image_animation::frame_count(buffer)))
+}
+
+/// 从文件头读取原始宽高和颜色类型, 不支持的格式返回 None.
+fn header(buffer: &[u8], format: ImageFormat) -> Option<((u32, u32), ColorType)> {
+    fn info<D: ImageDecoder<'static>>(decoder: ImageResult<D>) -> Option<((u32, u32), ColorType)> {
+        decoder.ok().map(|decoder| (decoder.dimensions(), decoder.color_type()))
+    }
+    let buffer = Cursor::new(buffer.to_vec());
+    match format {
+        ImageFormat::Png => info(PngDecoder::new(buffer)),
+        ImageFormat::Jpeg => info(JpegDecoder::new(buffer)),
+        ImageFormat::Gif => info(GifDecoder::new(buffer)),
+        ImageFormat::Bmp => info(BmpDecoder::new(buffer)),
+        ImageFormat::Tiff => info(TiffDecoder::new(buffer)),
+        ImageFormat::WebP => {
+            let features = BitstreamFeatures::new(buffer.get_ref())?;
+            let color = if features.has_alpha() { ColorType::Rgba8 } else { ColorType::Rgb8 };
+            Some(((features.width(), features.height()), color))
+        }
+        _ => None,
+    }
+}
+
+/// 根据原始宽高 (未矫正方向) 和颜色类型生成元信息.
+pub fn describe(buffer: &[u8], format: Option<ImageFormat>, (raw_width, raw_height): (u32, u32), color: ColorType,
+                orientation: Option<u32>, frame_count: usize) -> ImageMetadata {
+    let (width, height) = image_decode::oriented_dimensions(raw_width, raw_height, orientation);
+
+    let exif = exif::Reader::new().read_from_container(&mut Cursor::new(buffer)).ok();
+    let container = DynImage::from_bytes(Bytes::copy_from_slice(buffer)).ok().flatten();
+
+    ImageMetadata {
+        format: format.map(|value| format!("{:?}", value).to_lowercase()),
+        file_size: buffer.len(),
+        width,
+        height,
+        raw_width,
+        raw_height,
+        color_type: format!("{:?}", color),
+        bit_depth: color.bits_per_pixel() / color.channel_count() as u16,
+        has_alpha: color.has_alpha(),
+        frame_count,
+        progressive: container.as_ref().map(progressive).unwrap_or(false),
+        lossless: format.and_then(|value| lossless(value, buffer)),
+        dpi: exif.as_ref().and_then(exif_dpi).or_else(|| container.as_ref().and_then(container_dpi)),
+        icc_profile: container.as_ref().and_then(|value| value.icc_profile()).and_then(|profile| icc_description(&profile)),
+        exif: exif.as_ref().map(|value| exif_metadata(value, orientation)),
+    }
+}
+
+/// 渐进式 JPEG (SOF2、SOF6、SOF10、SOF14) 或交错 PNG.
+fn progressive(container: &DynImage) -> bool {
+    match container {
+        DynImage::Jpeg(jpeg) => jpeg.segments().iter().any(|segment| matches!(segment.marker(), 0xC2 | 0xC6 | 0xCA | 0xCE)),
+        DynImage::Png(png) => png.chunk_by_type(*b"IHDR").and_then(|chunk| chunk.contents().get(12).copied()) == Some(1),
+        DynImage::WebP(_) => false,
+    }
+}
+
+/// 是否无损编码, GIF 只能保存 256 色调色板, 按照有损处理; WebP 按照图像数据块 (动图为每一帧的 ANMF 子块) 是否都是 VP8L 判断.
+fn lossless(format: ImageFormat, buffer: &[u8]) -> Option<bool> {
+    match format {
+        ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Tiff => Some(true),
+        ImageFormat::Jpeg | ImageFormat::Gif => Some(false),
+        ImageFormat::WebP => {
+            let mut bitstreams = Vec::new();
+            for (id, data) in image_animation::webp_chunks(buffer) {
+                match &id {
+                    b"VP8 " | b"VP8L" => bitstreams.push(id),
+                    // ANMF 帧头 16 字节, 之后为帧数据子块
+                    b"ANMF" => bitstreams.extend(image_animation::riff_chunks(data, 16)
+                        .map(|(id, _)| id)
+                        .filter(|id| id == b"VP8 " || id == b"VP8L")),
+                    _ => {}
+                }
+            }
+            if bitstreams.is_empty() {
+                return None;
+            }
+            Some(bitstreams.iter().all(|id| id == b"VP8L"))
+        }
+        _ => None,
+    }
+}
+
+/// EXIF 中的分辨率.
+fn exif_dpi(exif: &exif::Exif) -> Option<Dpi> {
+    let rational = |tag| match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Rational(values) => values.first().map(|value| value.to_f64()),
+        _ => None,
+    };
+    // 2 英寸, 3 厘米
+    let unit = exif.get_field(exif::Tag::ResolutionUnit, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(2);
+    let scale = match unit {
+        2 => 1f64,
+        3 => 2.54f64,
+        _ => return None,
+    };
+    Some(Dpi { x: rational(exif::Tag::XResolution)? * scale, y: rational(exif::Tag::YResolution)? * scale })
+}
+
+/// JPEG JFIF 或 PNG pHYs 中的分辨率.
+fn container_dpi(container: &DynImage) -> Option<Dpi> {
+    match container {
+        DynImage::Jpeg(jpeg) => {
+            let contents = jpeg.segment_by_marker(0xE0)?.contents();
+            if !contents.starts_with(b"JFIF\0") || contents.len() < 12 {
+                return None;
+            }
+            let density = |index: usize| u16::from_be_bytes([contents[index], contents[index + 1]]) as f64;
+            let scale = match contents[7] {
+                1 => 1f64,
+                2 => 2.54f64,
+                _ => return None,
+            };
+            Some(Dpi { x: density(8) * scale, y: density(10) * scale })
+        }
+        DynImage::Png(png) => {
+            let contents = png.chunk_by_type(*b"pHYs")?.contents();
+            // 单位 1 为米
+            if contents.len() < 9 || contents[8] != 1 {
+                return None;
+            }
+            let density = |index: usize| u32::from_be_bytes([contents[index], contents[index + 1], contents[index + 2], contents[index + 3]]) as f64 * 0.0254;
+            Some(Dpi { x: density(0), y: density(4) })
+        }
+        DynImage::WebP(_) => None,
+    }
+}
+
+/// 读取 EXIF 相机、时间、GPS 信息.
+fn exif_metadata(exif: &exif::Exif, orientation: Option<u32>) -> ExifMetadata {
+    let ascii = |tag| match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Ascii(values) => values.first()
+            .map(|value| String::from_utf8_lossy(value).trim_end_matches('\0').trim().to_string())
+            .filter(|value| !value.is_empty()),
+        _ => None,
+    };
+    ExifMetadata {
+        make: ascii(exif::Tag::Make),
+        model: ascii(exif::Tag::Model),
+        date_time: ascii(exif::Tag::DateTimeOriginal).or_else(|| ascii(exif::Tag::DateTime)),
+        orientation,
+        gps: gps(exif),
+    }
+}
+
+/// 读取 GPS 坐标.
+fn gps(exif: &exif::Exif) -> Option<Gps> {
+    let rationals = |tag| match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Rational(values) => Some(values.iter().map(|value| value.to_f64()).collect::<Vec<f64>>()),
+        _ => None,
+    };
+    let reference = |tag| match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Ascii(values) => values.first().and_then(|value| value.first().copied()),
+        _ => None,
+    };
+    // 度、分、秒
+    let degrees = |tag, reference_tag, negative: u8| {
+        let values = rationals(tag)?;
+        let value = values.first()? + values.get(1).unwrap_or(&0f64) / 60f64 + values.get(2).unwrap_or(&0f64) / 3600f64;
+        Some(if reference(reference_tag) == Some(negative) { -value } else { value })
+    };
+    let altitude = rationals(exif::Tag::GPSAltitude).and_then(|values| values.first().copied()).map(|value| {
+        // 1 表示海平面以下
+        match exif.get_field(exif::Tag::GPSAltitudeRef, exif::In::PRIMARY).and_then(|field| field.value.get_uint(0)) {
+            Some(1) => -value,
+            _ => value,
+        }
+    });
+    Some(Gps {
+        latitude: degrees(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, b'S')?,
+        longitude: degrees(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, b'W')?,
+        altitude,
+    })
+}
+
+/// 读取 ICC 配置文件描述 (desc 标签, 支持 v2 textDescriptionType 和 v4 multiLocalizedUnicodeType).
+pub fn icc_description(profile: &[u8]) -> Option<String> {
+    let u32_at = |index: usize| profile.get(index..index + 4).map(|value| u32::from_be_bytes([value[0], value[1], value[2], value[3]]) as usize);
+    let count = u32_at(128)?;
+    let (offset, size) = (0..count.min(1024)).find_map(|index| {
+        let entry = 132 + index * 12;
+        match profile.get(entry..entry + 4) {
+            Some(b"desc") => Some((u32_at(entry + 4)?, u32_at(entry + 8)?)),
+            _ => None,
+        }
+    })?;
+    let tag = profile.get(offset..offset.checked_add(size)?)?;
+    let description = match tag.get(0..4)? {
+        b"desc" => {
+            let length = u32::from_be_bytes(tag.get(8..12)?.try_into().ok()?) as usize;
+            String::from_utf8_lossy(tag.get(12..length.checked_add(12)?)?).trim_end_matches('\0').to_string()
+        }
+        b"mluc" => {
+            // 使用第一条记录
+            let length = u32::from_be_bytes(tag.get(20..24)?.try_into().ok()?) as usize;
+            let start = u32::from_be_bytes(tag.get(24..28)?.try_into().ok()?) as usize;
+            let units = tag.get(start..start.checked_add(length)?)?.chunks_exact(2)
+                .map(|value| u16::from_be_bytes([value[0], value[1]]))
+                .collect::<Vec<u16>>();
+            String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+        }
+        _ => return None,
+    };
+    Some(description.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless_by_format() {
+        let webp = |chunk: &[u8]| [b"RIFF\0\0\0\0WEBP", chunk].concat();
+        // 有损数据中出现 VP8L 字节
+        assert_eq!(lossless(ImageFormat::WebP, &webp(b"VP8 \x04\0\0\0VP8L")), Some(false));
+        assert_eq!(lossless(ImageFormat::WebP, &webp(b"VP8L\x04\0\0\0\x2f\0\0\0")), Some(true));
+        assert_eq!(lossless(ImageFormat::WebP, &webp(b"VP8L\xff\xff\xff\xff")), None);
+        assert_eq!(lossless(ImageFormat::Gif, b"GIF89a"), Some(false));
+    }
+}
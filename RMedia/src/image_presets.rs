@@ -0,0 +1,135 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use serde::Deserialize;
+
+use crate::error::MediaError;
+use crate::image_style_compat;
+
+/// 引用预设的参数名.
+const STYLE: &str = "style";
+
+/// 预设配置: 参数字符串或者参数表.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PresetConfig {
+    Query(String),
+    Params(BTreeMap<String, PresetValue>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PresetValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl PresetValue {
+    fn into_string(self) -> String {
+        match self {
+            PresetValue::String(value) => value,
+            PresetValue::Integer(value) => value.to_string(),
+            PresetValue::Float(value) => value.to_string(),
+            PresetValue::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+/// 命名样式预设, 通过参数 style 引用, 调用时传入的其他参数覆盖预设中的同名参数.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Presets {
+    presets: BTreeMap<String, HashMap<String, String>>,
+}
+
+impl Presets {
+    /// 空预设.
+    pub const EMPTY: Presets = Presets { presets: BTreeMap::new() };
+
+    /// 解析 JSON (以 `{` 开头) 或 TOML 配置, 预设为参数字符串或者参数表:
+    ///
+    /// ```toml
+    /// avatar_small = "args=ZW100ACCC100&type=webp&quality=70"
+    ///
+    /// [feed_cover]
+    /// args = "ZW800ACCC300"
+    /// type = "webp"
+    /// quality = 75
+    /// ```
+    pub fn parse(config: &str) -> Result<Presets, MediaError> {
+        let configs: BTreeMap<String, PresetConfig> = if config.trim_start().starts_with('{') {
+            serde_json::from_str(config).map_err(|error| MediaError::InvalidParam(format!("invalid presets json: {}", error)))?
+        } else {
+            toml::from_str(config).map_err(|error| MediaError::InvalidParam(format!("invalid presets toml: {}", error)))?
+        };
+        let mut presets = BTreeMap::new();
+        for (name, config) in configs {
+            let pairs: Vec<(String, String)> = match config {
+                PresetConfig::Query(value) => url::form_urlencoded::parse(value.as_bytes()).into_owned().collect(),
+                PresetConfig::Params(values) => values.into_iter().map(|(key, value)| (key, value.into_string())).collect(),
+            };
+            let order = pairs.iter().map(|(key, _)| key.clone()).collect::<Vec<String>>();
+            let mut params: HashMap<String, String> = pairs.into_iter().collect();
+            if params.contains_key(STYLE) {
+                return Err(MediaError::InvalidParam(format!("preset `{}` cannot reference another preset", name)));
+            }
+            image_style_compat::translate(&mut params, &order)?;
+            presets.insert(name, params);
+        }
+        Ok(Presets { presets })
+    }
+
+    /// 预设名称 (排序).
+    pub fn names(&self) -> Vec<&str> {
+        self.presets.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// 读取预设, 不存在时错误信息列出所有预设.
+    pub fn get(&self, name: &str) -> Result<&HashMap<String, String>, MediaError> {
+        self.presets.get(name).ok_or_else(|| MediaError::UnknownPreset(format!("`{}`, available presets: [{}]", name, self.names().join(", "))))
+    }
+}
+
+/// 全局预设, 启动时从 Java 设置.
+static PRESETS: RwLock<Presets> = RwLock::new(Presets::EMPTY);
+
+/// 替换全局预设.
+pub fn set_presets(presets: Presets) {
+    *PRESETS.write().unwrap_or_else(|error| error.into_inner()) = presets;
+}
+
+/// 展开参数 style 引用的预设, 已传入的参数优先.
+pub fn expand(query_params_map: &mut HashMap<String, String>) -> Result<(), MediaError> {
+    let name = match query_params_map.remove(STYLE) {
+        None => return Ok(()),
+        Some(name) => name,
+    };
+    let presets = PRESETS.read().unwrap_or_else(|error| error.into_inner());
+    for (key, value) in presets.get(name.trim())? {
+        query_params_map.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_json_and_toml() {
+        let json = Presets::parse(r#"{"avatar_small": "args=ZW100ACCC100&type=webp", "feed_cover": {"args": "ZW800", "quality": 75}}"#).unwrap();
+        let toml = Presets::parse("avatar_small = \"args=ZW100ACCC100&type=webp\"\n\n[feed_cover]\nargs = \"ZW800\"\nquality = 75\n").unwrap();
+        assert_eq!(json, toml);
+        assert_eq!(json.names(), vec!["avatar_small", "feed_cover"]);
+        assert_eq!(json.get("feed_cover").unwrap()["quality"], "75");
+        assert!(Presets::parse(r#"{"a": "style=b"}"#).is_err());
+    }
+
+    #[test]
+    fn unknown_preset_lists_available() {
+        let presets = Presets::parse(r#"{"feed_cover": "args=ZW800", "avatar_small": "args=ZW100"}"#).unwrap();
+        let error = presets.get("feed").unwrap_err().to_string();
+        assert!(error.contains("`feed`") && error.contains("[avatar_small, feed_cover]"), "{}", error);
+    }
+}
@@ -1,5 +1,6 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::fs;
+use std::io::Cursor;
 
 use fast_qr::convert::Builder;
 use fast_qr::convert::image::ImageBuilder;
@@ -9,8 +10,13 @@ use image::imageops::Lanczos3;
 use url::Url;
 
 use crate::error::MediaError;
+use crate::image_cache::CacheKey;
+use crate::{image_cache, image_decode, image_limits, image_sandbox, image_style};
 
-pub struct QrCode {
+/// Logo 间距最大值 (与 Logo 大小 500 相同).
+const MAX_LOGO_MARGIN: u32 = 500;
+
+pub struct QrCode<'a> {
     /// 二维码内容.
     pub text: String,
 
@@ -24,9 +30,10 @@ pub struct QrCode {
     pub quality: u8,
 
     /// Logo 文件.
-    pub logo: Option<Vec<u8>>,
+    pub logo: Option<Cow<'a, [u8]>>,
 
     /// Logo 路径.
+    #[allow(dead_code)]
     pub logo_path: Option<String>,
 
     /// Logo 大小.
@@ -36,9 +43,10 @@ pub struct QrCode {
     pub logo_margin: Option<u32>,
 
     /// 海报 文件.
-    pub poster: Option<Vec<u8>>,
+    pub poster: Option<Cow<'a, [u8]>>,
 
     /// 海报 文件.
+    #[allow(dead_code)]
     pub poster_path: Option<String>,
 
     /// 二维码在海报的位置:X .
@@ -46,10 +54,13 @@ pub struct QrCode {
 
     /// 二维码在海报的位置:Y .
     pub poster_qr_code_y: Option<u32>,
+
+    /// Logo、海报按照 EXIF 方向矫正.
+    pub auto_orient: bool,
 }
 
 /// 创建QRCode 实例.
-pub fn new(query_params: String, logo: Option<Vec<u8>>, poster: Option<Vec<u8>>) -> Result<QrCode, MediaError> {
+pub fn new<'a>(query_params: String, logo: Option<&'a [u8]>, poster: Option<&'a [u8]>) -> Result<QrCode<'a>, MediaError> {
     let url = format!("https://www.rust-lang.org?{}", query_params);
     let url = Url::parse(&url)?;
     let mut query_params_map = HashMap::new();
@@ -61,16 +72,16 @@ pub fn new(query_params: String, logo: Option<Vec<u8>>, poster: Option<Vec<u8>>)
     let default_value_400 = String::from("400");
     let default_value_1 = String::from("1");
     Ok(QrCode {
-        text: query_params_map.get("text").ok_or(MediaError::Error("text not found".to_string()))?.to_string(),
+        text: query_params_map.get("text").ok_or(MediaError::InvalidParam("text not found".to_string()))?.to_string(),
         size: query_params_map.get("size").unwrap_or(&default_value_400).parse::<u32>()?,
         margin: query_params_map.get("margin").unwrap_or(&default_value_1).parse::<u8>()?,
         quality: query_params_map.get("quality").unwrap_or(&default_value_1).parse::<u8>()?,
         logo: match logo {
-            Some(_) => logo,
+            Some(value) => Some(Cow::Borrowed(value)),
             None => {
                 match query_params_map.get("logo_path") {
                     Some(path) => {
-                        Some(fs::read(path)?)
+                        Some(Cow::Owned(image_sandbox::read_file(path)?))
                     }
                     None => None,
                 }
@@ -83,14 +94,14 @@ pub fn new(query_params: String, logo: Option<Vec<u8>>, poster: Option<Vec<u8>>)
         },
         logo_margin: match query_params_map.get("logo_margin") {
             None => None,
-            Some(v) => Some(v.parse::<u32>()?)
+            Some(v) => Some(v.parse::<u32>()?.min(MAX_LOGO_MARGIN))
         },
         poster: match poster {
-            Some(_) => poster,
+            Some(value) => Some(Cow::Borrowed(value)),
             None => {
                 match query_params_map.get("poster_path") {
                     Some(path) => {
-                        Some(fs::read(path)?)
+                        Some(Cow::Owned(image_sandbox::read_file(path)?))
                     }
                     None => None,
                 }
@@ -105,10 +116,11 @@ pub fn new(query_params: String, logo: Option<Vec<u8>>, poster: Option<Vec<u8>>)
             None => None,
             Some(v) => Some(v.parse::<u32>()?)
         },
+        auto_orient: image_style::query_bool(&query_params_map, "auto_orient", true)?,
     })
 }
 
-impl QrCode {
+impl QrCode<'_> {
 
     /// 生成二维码.
     pub fn generate(&self) -> Result<DynamicImage, MediaError> {
@@ -116,20 +128,38 @@ impl QrCode {
         let result = match &self.poster {
             None => qr_code,
             Some(poster) => {
-                let mut poster = image::load_from_memory(&poster)?;
-                self.copy(&mut poster, match self.poster_qr_code_x {
-                    None => 0,
-                    Some(value) => value
-                }, match self.poster_qr_code_y {
-                    None => 0,
-                    Some(value) => value
-                }, qr_code);
+                let mut poster = image_decode::load_from_memory(poster, self.auto_orient)?;
+                image_limits::limits().check_dimensions(poster.width(), poster.height())?;
+                self.copy(&mut poster, self.poster_qr_code_x.unwrap_or_default(), self.poster_qr_code_y.unwrap_or_default(), qr_code);
                 poster
             }
         };
         Ok(result)
     }
 
+    /// 生成二维码 PNG, 开启缓存时相同的参数、Logo、海报使用缓存结果.
+    pub fn generate_png(&self) -> Result<Vec<u8>, MediaError> {
+        image_cache::cached(|| self.cache_key(), || {
+            let mut buffer = Cursor::new(Vec::new());
+            self.generate()?.write_to(&mut buffer, image::ImageOutputFormat::Png)?;
+            Ok(buffer.into_inner())
+        })
+    }
+
+    fn cache_key(&self) -> CacheKey {
+        let number = |value: Option<u32>| value.map(u32::to_le_bytes);
+        CacheKey::new("qr_code")
+            .bytes(self.text.as_bytes())
+            .bytes(&self.size.to_le_bytes())
+            .bytes(&[self.margin, self.quality, self.auto_orient as u8])
+            .optional(self.logo.as_deref())
+            .optional(number(self.logo_size).as_ref().map(|value| value.as_slice()))
+            .optional(number(self.logo_margin).as_ref().map(|value| value.as_slice()))
+            .optional(self.poster.as_deref())
+            .optional(number(self.poster_qr_code_x).as_ref().map(|value| value.as_slice()))
+            .optional(number(self.poster_qr_code_y).as_ref().map(|value| value.as_slice()))
+    }
+
     fn qr_code_generate(&self) -> Result<DynamicImage, MediaError> {
         // 计算图片质量
         let image_quality: u8;
@@ -157,41 +187,43 @@ impl QrCode {
             .background_color([255, 255, 255, 255])
             .margin(self.margin as usize)
             .fit_width(qr_image_size)
-            .to_bytes(&QRBuilder::new((&self.text).to_string()).build()?)?;
+            .to_bytes(&QRBuilder::new(self.text.to_string()).build()?)?;
 
         let mut qr_image = image::load_from_memory(&qr_image)?;
         match &self.logo {
             None => {}
             Some(login_image) => {
-                let mut logo = self.create_logo(image::load_from_memory(&login_image)?, self.logo_margin, image_quality)?;
+                let mut logo = self.create_logo(image_decode::load_from_memory(login_image, self.auto_orient)?, self.logo_margin, image_quality)?;
+                // Logo 不超过二维码大小
                 let logo_size = match &self.logo_size {
                     None => (qr_image_size as f32 / 3.1) as u32,
-                    Some(value) => *value
+                    Some(value) => (*value).min(qr_image_size)
                 };
+                image_limits::limits().check_dimensions(logo_size, logo_size)?;
                 logo = logo.resize(logo_size, logo_size, Lanczos3);
-                let x_position = (qr_image_size - logo.width()) / 2;
+                let x_position = qr_image_size.checked_sub(logo.width())
+                    .ok_or(MediaError::InvalidParam(format!("logo {}px larger than qr code {}px", logo.width(), qr_image_size)))? / 2;
                 let y_position = x_position;
                 self.copy(&mut qr_image, x_position, y_position, logo);
             }
         }
 
         // 压缩返回图片
-        return Ok(qr_image.resize(image_size, image_size, Lanczos3));
+        Ok(qr_image.resize(image_size, image_size, Lanczos3))
     }
 
     fn create_logo(&self, logo: DynamicImage, logo_margin: Option<u32>, image_quality: u8) -> Result<DynamicImage, MediaError> {
         let logo_size = 500;
-        let logo_border = match logo_margin {
-            None => 30,
-            Some(value) => value
-        };
+        let logo_border = logo_margin.unwrap_or(30);
         // Logo 不管多大或者多小默认500
         let mut logo = logo.resize(logo_size, logo_size, Lanczos3);
 
         // 创建圆角矩形
-        let rectangle_size = logo_size + logo_border * 2;
-        let rectangle_width = rectangle_size * image_quality as u32;
+        let overflow = || MediaError::LimitExceeded(format!("logo margin {} too large", logo_border));
+        let rectangle_size = logo_border.checked_mul(2).and_then(|border| border.checked_add(logo_size)).ok_or_else(overflow)?;
+        let rectangle_width = rectangle_size.checked_mul(image_quality as u32).ok_or_else(overflow)?;
         let rectangle_height = rectangle_width;
+        image_limits::limits().check_dimensions(rectangle_width, rectangle_height)?;
         // 圆角 border-radius 的半径
         let rectangle_border_radius = 60 * image_quality as u32;
         // 绘制矩形
@@ -200,18 +232,14 @@ impl QrCode {
             for x in 0..rectangle_width {
                 let x_distance = if x < rectangle_border_radius {
                     rectangle_border_radius - x
-                } else if x >= rectangle_width - rectangle_border_radius {
-                    x - (rectangle_width - rectangle_border_radius)
                 } else {
-                    0
+                    x.saturating_sub(rectangle_width - rectangle_border_radius)
                 };
 
                 let y_distance = if y < rectangle_border_radius {
                     rectangle_border_radius - y
-                } else if y >= rectangle_height - rectangle_border_radius {
-                    y - (rectangle_height - rectangle_border_radius)
                 } else {
-                    0
+                    y.saturating_sub(rectangle_height - rectangle_border_radius)
                 };
 
                 let distance_to_corner = ((x_distance.pow(2) + y_distance.pow(2)) as f32).sqrt() as u32;
@@ -238,18 +266,14 @@ impl QrCode {
             for x in 0..logo_size {
                 let x_distance = if x < logo_border_radius {
                     logo_border_radius - x
-                } else if x >= logo_size - logo_border_radius {
-                    x - (logo_size - logo_border_radius)
                 } else {
-                    0
+                    x.saturating_sub(logo_size - logo_border_radius)
                 };
 
                 let y_distance = if y < logo_border_radius {
                     logo_border_radius - y
-                } else if y >= logo_size - logo_border_radius {
-                    y - (logo_size - logo_border_radius)
                 } else {
-                    0
+                    y.saturating_sub(logo_size - logo_border_radius)
                 };
 
                 let distance_to_corner = ((x_distance.pow(2) + y_distance.pow(2)) as f32).sqrt() as u32;
@@ -264,7 +288,7 @@ impl QrCode {
         // 合并图片
         self.copy(&mut image, logo_border, logo_border, logo);
 
-        return Ok(image.resize(logo_size, logo_size, Lanczos3));
+        Ok(image.resize(logo_size, logo_size, Lanczos3))
     }
 
     fn copy(&self, target: &mut DynamicImage, x_position: u32, y_position: u32, source: DynamicImage) {
@@ -277,4 +301,15 @@ impl QrCode {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_logo_margin() {
+        let qr_code = new("text=a&logo_margin=4294967295".to_string(), None, None).unwrap();
+        assert_eq!(qr_code.logo_margin, Some(MAX_LOGO_MARGIN));
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File, Metadata};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::error::MediaError;
+use crate::{image_limits, image_style};
+
+/// 文件沙箱, 作用于所有按照路径读写文件的入口 (from、to、logo_path、poster_path、icc).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sandbox {
+    /// 允许按照路径读写文件, 关闭后只能通过二进制处理.
+    pub path_io: bool,
+
+    /// 允许访问的根目录 (已规范化), 为空时不限制.
+    pub roots: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    /// 默认沙箱: 允许路径读写, 不限制目录.
+    pub const DEFAULT: Sandbox = Sandbox { path_io: true, roots: Vec::new() };
+
+    /// 读取参数 path_io (默认 true) 和 roots (多个目录使用系统路径分隔符连接, Linux 为 `:`, Windows 为 `;`).
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<Sandbox, MediaError> {
+        let path_io = image_style::query_bool(query_params_map, "path_io", true)?;
+        let mut roots = vec![];
+        if let Some(value) = query_params_map.get("roots") {
+            for root in env::split_paths(value).filter(|root| !root.as_os_str().is_empty()) {
+                let canonical = root.canonicalize()
+                    .map_err(|error| MediaError::InvalidParam(format!("sandbox root `{}` invalid: {}", root.display(), error)))?;
+                if !canonical.is_dir() {
+                    return Err(MediaError::InvalidParam(format!("sandbox root `{}` is not a directory", root.display())));
+                }
+                roots.push(canonical);
+            }
+        }
+        Ok(Sandbox { path_io, roots })
+    }
+
+    /// 检查读取路径, 返回解析符号链接后的真实路径.
+    pub fn check_read<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, MediaError> {
+        let path = path.as_ref();
+        self.check_path_io(path)?;
+        let canonical = path.canonicalize()?;
+        self.check_root(path, &canonical)?;
+        Ok(canonical)
+    }
+
+    /// 检查写入路径, 返回真实路径, 目标文件已存在且为符号链接时按照链接目标检查.
+    pub fn check_write<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, MediaError> {
+        let path = path.as_ref();
+        self.check_path_io(path)?;
+        let name = match path.components().next_back() {
+            Some(Component::Normal(name)) => name,
+            _ => return Err(denied(path, "not a file")),
+        };
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut canonical = parent.canonicalize()?.join(name);
+        if fs::symlink_metadata(&canonical).map(|metadata| metadata.file_type().is_symlink()).unwrap_or(false) {
+            canonical = canonical.canonicalize()?;
+        }
+        if canonical.is_dir() {
+            return Err(denied(path, "is a directory"));
+        }
+        self.check_root(path, &canonical)?;
+        Ok(canonical)
+    }
+
+    fn check_path_io(&self, path: &Path) -> Result<(), MediaError> {
+        if !self.path_io {
+            return Err(denied(path, "path io disabled"));
+        }
+        Ok(())
+    }
+
+    fn check_root(&self, path: &Path, canonical: &Path) -> Result<(), MediaError> {
+        if !self.roots.is_empty() && !self.roots.iter().any(|root| canonical.starts_with(root)) {
+            return Err(denied(path, "outside allowed roots"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Sandbox::DEFAULT
+    }
+}
+
+/// 全局沙箱, 启动时从 Java 设置一次.
+static SANDBOX: RwLock<Sandbox> = RwLock::new(Sandbox::DEFAULT);
+
+/// 当前沙箱.
+pub fn sandbox() -> Sandbox {
+    SANDBOX.read().unwrap_or_else(|error| error.into_inner()).clone()
+}
+
+/// 替换全局沙箱.
+pub fn set_sandbox(sandbox: Sandbox) {
+    *SANDBOX.write().unwrap_or_else(|error| error.into_inner()) = sandbox;
+}
+
+/// 按照沙箱检查路径, 检查文件大小后读取文件.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, MediaError> {
+    Ok(read_file_with_metadata(path)?.0)
+}
+
+/// 按照沙箱检查路径后读取文件, 同时返回真实路径和读取前从同一个文件句柄获取的元数据.
+pub fn read_file_with_metadata<P: AsRef<Path>>(path: P) -> Result<(Vec<u8>, PathBuf, Metadata), MediaError> {
+    let path = sandbox().check_read(path)?;
+    let mut file = File::open(&path)?;
+    let metadata = file.metadata()?;
+    let limits = image_limits::limits();
+    limits.check_bytes(metadata.len())?;
+    // 读取期间文件变大时最多多读一个字节, 超出限制返回错误
+    let mut buffer = Vec::with_capacity(metadata.len() as usize);
+    file.by_ref().take(limits.max_input_bytes.saturating_add(1)).read_to_end(&mut buffer)?;
+    limits.check_bytes(buffer.len() as u64)?;
+    Ok((buffer, path, metadata))
+}
+
+/// 按照沙箱检查路径后写入文件.
+pub fn write_file<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<(), MediaError> {
+    let path = sandbox().check_write(path)?;
+    Ok(fs::write(path, data)?)
+}
+
+fn denied(path: &Path, reason: &str) -> MediaError {
+    MediaError::PathDenied(format!("`{}` {}", path.display(), reason))
+}
+
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    /// 临时目录: root/inside.png, outside/secret.png, root/link.png → outside/secret.png, root/escape → outside.
+    fn fixture(name: &str) -> (PathBuf, PathBuf, Sandbox) {
+        let dir = env::temp_dir().join(format!("rmedia-sandbox-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (root, outside) = (dir.join("root"), dir.join("outside"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("inside.png"), b"inside").unwrap();
+        fs::write(outside.join("secret.png"), b"secret").unwrap();
+        symlink(outside.join("secret.png"), root.join("link.png")).unwrap();
+        symlink(&outside, root.join("escape")).unwrap();
+        let query = HashMap::from([("roots".to_string(), root.display().to_string())]);
+        (dir, root, Sandbox::new(&query).unwrap())
+    }
+
+    #[test]
+    fn reject_parent_traversal() {
+        let (dir, root, sandbox) = fixture("traversal");
+        assert!(sandbox.check_read(root.join("inside.png")).is_ok());
+        assert!(matches!(sandbox.check_read(root.join("../outside/secret.png")), Err(MediaError::PathDenied(_))));
+        assert!(matches!(sandbox.check_write(root.join("../outside/new.png")), Err(MediaError::PathDenied(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reject_symlink_escape() {
+        let (dir, root, sandbox) = fixture("symlink");
+        assert!(matches!(sandbox.check_read(root.join("link.png")), Err(MediaError::PathDenied(_))));
+        assert!(matches!(sandbox.check_read(root.join("escape/secret.png")), Err(MediaError::PathDenied(_))));
+        assert!(matches!(sandbox.check_write(root.join("link.png")), Err(MediaError::PathDenied(_))));
+        assert!(matches!(sandbox.check_write(root.join("escape/new.png")), Err(MediaError::PathDenied(_))));
+        assert_eq!(sandbox.check_write(root.join("new.png")).unwrap(), root.canonicalize().unwrap().join("new.png"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reject_path_io_disabled() {
+        let (dir, root, sandbox) = fixture("path-io");
+        let sandbox = Sandbox { path_io: false, ..sandbox };
+        assert!(matches!(sandbox.check_read(root.join("inside.png")), Err(MediaError::PathDenied(_))));
+        assert!(matches!(sandbox.check_write(root.join("new.png")), Err(MediaError::PathDenied(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use exif::experimental::Writer;
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+use img_parts::jpeg::{Jpeg, JpegSegment, markers};
+use img_parts::png::{Png, PngChunk};
+use img_parts::riff::{RiffChunk, RiffContent};
+use img_parts::webp::WebP;
+
+use crate::error::MediaError;
+use crate::image_encode::OutputFormat;
+
+const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+const XMP_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+const ICC_PREFIX: &[u8] = b"ICC_PROFILE\0";
+
+/// JPEG 段内容最大长度 (不包含长度字段).
+const JPEG_SEGMENT_MAX: usize = 65533;
+
+/// 元数据保留策略.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StripPolicy {
+    /// 删除全部元数据 (默认).
+    #[default]
+    All,
+
+    /// 只删除 GPS 信息.
+    GpsOnly,
+
+    /// 只保留 ICC 配置文件.
+    KeepIcc,
+
+    /// 保留 EXIF、ICC、XMP.
+    KeepAll,
+}
+
+impl StripPolicy {
+    /// 解析策略名称: all、gps-only、keep-icc、keep-all.
+    pub fn parse(value: &str) -> Result<StripPolicy, MediaError> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "all" => Ok(StripPolicy::All),
+            "gps-only" => Ok(StripPolicy::GpsOnly),
+            "keep-icc" => Ok(StripPolicy::KeepIcc),
+            "keep-all" => Ok(StripPolicy::KeepAll),
+            _ => Err(MediaError::InvalidParam(format!("invalid strip policy: {}", value))),
+        }
+    }
+
+    /// 保留元数据的策略只支持 JPEG、PNG、WebP, 其他格式返回 UnsupportedFormat.
+    pub fn check(self, format: OutputFormat) -> Result<(), MediaError> {
+        match (self, format) {
+            (StripPolicy::All, _) | (_, OutputFormat::Jpeg | OutputFormat::Png | OutputFormat::WebP) => Ok(()),
+            _ => Err(MediaError::UnsupportedFormat(format!("{:?} with strip={:?}, only jpeg, png and webp keep metadata", format, self))),
+        }
+    }
+
+    /// 读取参数 strip.
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<StripPolicy, MediaError> {
+        match query_params_map.get("strip") {
+            None => Ok(StripPolicy::default()),
+            Some(value) => StripPolicy::parse(value),
+        }
+    }
+}
+
+/// 写入输出图片的元数据.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    /// EXIF (TIFF 结构, 不包含 Exif 前缀).
+    pub exif: Option<Vec<u8>>,
+
+    pub icc: Option<Vec<u8>>,
+
+    pub xmp: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.icc.is_none() && self.xmp.is_none()
+    }
+}
+
+/// 按照策略读取源图片 (JPEG、PNG、WebP) 中需要保留的元数据, oriented 表示输出图片已经按照 EXIF 方向矫正.
+/// EXIF 会重新生成: 删除缩略图、像素尺寸, 已矫正时删除方向, gps-only 时删除 GPS (XMP 中删除 exif:GPS* 属性).
+pub fn extract(buffer: &[u8], policy: StripPolicy, oriented: bool) -> Metadata {
+    if policy == StripPolicy::All {
+        return Metadata::default();
+    }
+    let container = match DynImage::from_bytes(Bytes::copy_from_slice(buffer)) {
+        Ok(Some(value)) => value,
+        _ => return Metadata::default(),
+    };
+    let icc = container.icc_profile().map(|value| value.to_vec());
+    if policy == StripPolicy::KeepIcc {
+        return Metadata { icc, ..Metadata::default() };
+    }
+    let strip_gps = policy == StripPolicy::GpsOnly;
+    let exif = container_exif(&container).and_then(|value| rewrite_exif(value, strip_gps, oriented));
+    let xmp = container_xmp(&container)
+        .and_then(|value| if strip_gps { strip_xmp_gps(&value) } else { Some(value) });
+    Metadata { exif, icc, xmp }
+}
+
+/// 将元数据写入编码结果, 只支持 JPEG、PNG、WebP, 其他格式不写入任何元数据.
+pub fn embed(data: Vec<u8>, format: OutputFormat, metadata: &Metadata) -> Result<Vec<u8>, MediaError> {
+    if metadata.is_empty() {
+        return Ok(data);
+    }
+    let error = |error: img_parts::Error| MediaError::Error(format!("metadata embed fail: {}", error));
+    match format {
+        OutputFormat::Jpeg => embed_jpeg(Jpeg::from_bytes(Bytes::from(data)).map_err(error)?, metadata),
+        OutputFormat::Png => embed_png(Png::from_bytes(Bytes::from(data)).map_err(error)?, metadata),
+        OutputFormat::WebP => embed_webp(WebP::from_bytes(Bytes::from(data)).map_err(error)?, metadata),
+        _ => Ok(data),
+    }
+}
+
+/// 读取 EXIF, WebP 的 EXIF 块可能带有 Exif 前缀.
+fn container_exif(container: &DynImage) -> Option<Vec<u8>> {
+    match container {
+        DynImage::WebP(webp) => {
+            let data = webp.chunk_by_id(*b"EXIF")?.content().data()?;
+            Some(data.strip_prefix(EXIF_PREFIX).unwrap_or(data).to_vec())
+        }
+        container => container.exif().map(|value| value.to_vec()),
+    }
+}
+
+/// 读取 XMP (JPEG APP1、PNG iTXt、WebP XMP 块).
+fn container_xmp(container: &DynImage) -> Option<Vec<u8>> {
+    match container {
+        DynImage::Jpeg(jpeg) => jpeg.segments_by_marker(markers::APP1)
+            .find_map(|segment| segment.contents().strip_prefix(XMP_PREFIX).map(|value| value.to_vec())),
+        DynImage::Png(png) => png.chunks_by_type(*b"iTXt").find_map(|chunk| {
+            // 关键字\0 压缩标记 压缩方式 语言\0 翻译关键字\0 文本, 只支持未压缩
+            let contents = chunk.contents().strip_prefix(XMP_KEYWORD)?.strip_prefix(b"\0")?;
+            if contents.first() != Some(&0) {
+                return None;
+            }
+            let mut text = contents.get(2..)?;
+            for _ in 0..2 {
+                text = &text[text.iter().position(|value| *value == 0)? + 1..];
+            }
+            Some(text.to_vec())
+        }),
+        DynImage::WebP(webp) => Some(webp.chunk_by_id(*b"XMP ")?.content().data()?.to_vec()),
+    }
+}
+
+/// XMP 中 GPS 属性的前缀.
+const XMP_GPS: &str = "exif:GPS";
+
+/// 删除 XMP 中的 exif:GPS* 属性 (元素和属性两种写法), 无法解析时不保留 XMP.
+fn strip_xmp_gps(xmp: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(xmp).ok()?;
+    let name_end = |text: &str, start: usize| start + text[start..]
+        .find(|value: char| !(value.is_ascii_alphanumeric() || matches!(value, '_' | '-' | '.')))
+        .unwrap_or(text.len() - start);
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(XMP_GPS) {
+        let before = &rest[..index];
+        let end = name_end(rest, index + XMP_GPS.len());
+        let name = &rest[index..end];
+        if let Some(before) = before.strip_suffix('<') {
+            // <exif:GPSLatitude>...</exif:GPSLatitude> 或 <exif:GPSLatitude .../>
+            let open = end + rest[end..].find('>')?;
+            let close = if rest[..open].ends_with('/') {
+                open + 1
+            } else {
+                let closing = format!("</{}>", name);
+                open + rest[open..].find(&closing)? + closing.len()
+            };
+            output.push_str(before);
+            rest = &rest[close..];
+        } else if before.ends_with(|value: char| value.is_ascii_whitespace()) {
+            // exif:GPSLatitude="..."
+            let value = rest[end..].trim_start().strip_prefix('=')?.trim_start();
+            let quote = value.chars().next().filter(|value| matches!(value, '"' | '\''))?;
+            let value_end = rest.len() - value.len() + 1 + value[1..].find(quote)? + 1;
+            output.push_str(before.trim_end());
+            rest = &rest[value_end..];
+        } else {
+            output.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    output.push_str(rest);
+    Some(output.into_bytes())
+}
+
+/// 重新生成 EXIF, 只保留主图片 IFD.
+fn rewrite_exif(raw: Vec<u8>, strip_gps: bool, oriented: bool) -> Option<Vec<u8>> {
+    let exif = exif::Reader::new().read_raw(raw).ok()?;
+    let fields = exif.fields()
+        .filter(|field| field.ifd_num == exif::In::PRIMARY)
+        .filter(|field| !(strip_gps && field.tag.0 == exif::Context::Gps))
+        .filter(|field| !(oriented && field.tag == exif::Tag::Orientation))
+        .filter(|field| !matches!(field.tag, exif::Tag::PixelXDimension | exif::Tag::PixelYDimension
+            | exif::Tag::ImageWidth | exif::Tag::ImageLength))
+        .collect::<Vec<&exif::Field>>();
+    if fields.is_empty() {
+        return None;
+    }
+    let mut writer = Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    let mut buffer = Cursor::new(Vec::new());
+    writer.write(&mut buffer, exif.little_endian()).ok()?;
+    Some(buffer.into_inner())
+}
+
+/// 写入 JPEG: APP0 之后依次写入 EXIF (APP1)、XMP (APP1)、ICC (APP2), 超过段长度限制的 EXIF、XMP 不写入.
+fn embed_jpeg(mut jpeg: Jpeg, metadata: &Metadata) -> Result<Vec<u8>, MediaError> {
+    let mut segments = Vec::new();
+    if let Some(exif) = metadata.exif.as_ref().filter(|value| value.len() + EXIF_PREFIX.len() <= JPEG_SEGMENT_MAX) {
+        segments.push(JpegSegment::new_with_contents(markers::APP1, Bytes::from([EXIF_PREFIX, exif].concat())));
+    }
+    if let Some(xmp) = metadata.xmp.as_ref().filter(|value| value.len() + XMP_PREFIX.len() <= JPEG_SEGMENT_MAX) {
+        segments.push(JpegSegment::new_with_contents(markers::APP1, Bytes::from([XMP_PREFIX, xmp].concat())));
+    }
+    if let Some(icc) = &metadata.icc {
+        // 前缀 + 序号 + 总数
+        let parts = icc.chunks(JPEG_SEGMENT_MAX - ICC_PREFIX.len() - 2).collect::<Vec<&[u8]>>();
+        if parts.len() > u8::MAX as usize {
+            return Err(MediaError::Error("icc profile too large".to_string()));
+        }
+        for (index, part) in parts.iter().enumerate() {
+            let contents = [ICC_PREFIX, &[index as u8 + 1, parts.len() as u8], part].concat();
+            segments.push(JpegSegment::new_with_contents(markers::APP2, Bytes::from(contents)));
+        }
+    }
+    let position = match jpeg.segments().first() {
+        Some(segment) if segment.marker() == markers::APP0 => 1,
+        _ => 0,
+    };
+    jpeg.segments_mut().splice(position..position, segments);
+    Ok(jpeg.encoder().bytes().to_vec())
+}
+
+/// 写入 PNG: iCCP 在 IHDR 之后, eXIf、iTXt 在 IDAT 之前.
+fn embed_png(mut png: Png, metadata: &Metadata) -> Result<Vec<u8>, MediaError> {
+    if let Some(icc) = &metadata.icc {
+        png.set_icc_profile(Some(Bytes::from(icc.clone())));
+    }
+    let mut chunks = Vec::new();
+    if let Some(exif) = &metadata.exif {
+        chunks.push(PngChunk::new(*b"eXIf", Bytes::from(exif.clone())));
+    }
+    if let Some(xmp) = &metadata.xmp {
+        let contents = [XMP_KEYWORD, b"\0\0\0\0\0", xmp].concat();
+        chunks.push(PngChunk::new(*b"iTXt", Bytes::from(contents)));
+    }
+    let position = png.chunks().iter().position(|chunk| chunk.kind() == *b"IDAT").unwrap_or(1);
+    png.chunks_mut().splice(position..position, chunks);
+    Ok(png.encoder().bytes().to_vec())
+}
+
+/// 写入 WebP: 转换为扩展格式 (VP8X), 块顺序为 VP8X、ICCP、图片数据 (ANIM/ANMF 或 ALPH/VP8/VP8L)、EXIF、XMP.
+fn embed_webp(webp: WebP, metadata: &Metadata) -> Result<Vec<u8>, MediaError> {
+    let (width, height) = webp_dimensions(&webp).ok_or(MediaError::Error("webp dimensions not found".to_string()))?;
+    let mut alpha = webp.has_chunk(*b"ALPH");
+    if let Some(data) = webp.chunk_by_id(*b"VP8X").and_then(|chunk| chunk.content().data()) {
+        alpha |= data.first().map(|flags| flags & 0x10 != 0).unwrap_or(false);
+    }
+    if let Some(data) = webp.chunk_by_id(*b"VP8L").and_then(|chunk| chunk.content().data()) {
+        // 签名之后 14 位宽、14 位高、1 位透明
+        alpha |= data.get(4).map(|value| value & 0x10 != 0).unwrap_or(false);
+    }
+    let animation = webp.has_chunk(*b"ANIM");
+
+    let mut flags = 0u8;
+    for (enabled, flag) in [(metadata.icc.is_some(), 0x20), (alpha, 0x10), (metadata.exif.is_some(), 0x08),
+                            (metadata.xmp.is_some(), 0x04), (animation, 0x02)] {
+        if enabled {
+            flags |= flag;
+        }
+    }
+    // VP8X 中保存宽高减 1, 宽高为 0 的数据无效
+    let (width, height) = width.checked_sub(1).zip(height.checked_sub(1))
+        .ok_or_else(|| MediaError::DecodeError(format!("invalid webp dimensions {}x{}", width, height)))?;
+    let mut header = vec![flags, 0, 0, 0];
+    header.extend_from_slice(&width.to_le_bytes()[..3]);
+    header.extend_from_slice(&height.to_le_bytes()[..3]);
+
+    let data = |id: [u8; 4], value: &Vec<u8>| RiffChunk::new(id, RiffContent::Data(Bytes::from(value.clone())));
+    let mut chunks = vec![RiffChunk::new(*b"VP8X", RiffContent::Data(Bytes::from(header)))];
+    chunks.extend(metadata.icc.iter().map(|value| data(*b"ICCP", value)));
+    chunks.extend(webp.chunks().iter()
+        .filter(|chunk| !matches!(&chunk.id(), b"VP8X" | b"ICCP" | b"EXIF" | b"XMP "))
+        .cloned());
+    chunks.extend(metadata.exif.iter().map(|value| data(*b"EXIF", value)));
+    chunks.extend(metadata.xmp.iter().map(|value| data(*b"XMP ", value)));
+    let riff = RiffChunk::new(*b"RIFF", RiffContent::List { kind: Some(*b"WEBP"), subchunks: chunks });
+    Ok(riff.encoder().bytes().to_vec())
+}
+
+/// WebP 画布宽高, VP8X 为 标记(1) + 保留(3) + 宽(3) + 高(3), 没有 VP8X 时读取图片数据.
+fn webp_dimensions(webp: &WebP) -> Option<(u32, u32)> {
+    match webp.chunk_by_id(*b"VP8X").and_then(|chunk| chunk.content().data()) {
+        Some(data) => {
+            let value = |index: usize| data.get(index..index + 3).map(|value| u32::from_le_bytes([value[0], value[1], value[2], 0]) + 1);
+            Some((value(4)?, value(7)?))
+        }
+        None => webp.dimensions(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_gps_properties_from_xmp() {
+        let xmp = r#"<rdf:Description exif:GPSLatitude="30,1N" exif:GPSLongitude='120,2E' tiff:Make="GPS Inc"><exif:GPSAltitude>12/1</exif:GPSAltitude><exif:GPSVersionID/><dc:title>GPS trip</dc:title></rdf:Description>"#;
+        let stripped = String::from_utf8(strip_xmp_gps(xmp.as_bytes()).unwrap()).unwrap();
+        assert_eq!(stripped, r#"<rdf:Description tiff:Make="GPS Inc"><dc:title>GPS trip</dc:title></rdf:Description>"#);
+        assert!(strip_xmp_gps(b"<exif:GPSAltitude>12/1").is_none());
+    }
+
+    #[test]
+    fn keep_policies_need_metadata_format() {
+        assert!(StripPolicy::KeepAll.check(OutputFormat::Jpeg).is_ok());
+        assert!(StripPolicy::All.check(OutputFormat::Gif).is_ok());
+        assert!(matches!(StripPolicy::KeepIcc.check(OutputFormat::Avif), Err(MediaError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn reject_zero_webp_dimensions() {
+        // 有损 VP8: 帧标记(3) + 起始码(3) + 宽(2) + 高(2), 宽高为 0
+        let vp8 = [0x10, 0x02, 0x00, 0x9d, 0x01, 0x2a, 0x00, 0x00, 0x00, 0x00];
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(4 + 8 + vp8.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"WEBPVP8 ");
+        data.extend_from_slice(&(vp8.len() as u32).to_le_bytes());
+        data.extend_from_slice(&vp8);
+        let metadata = Metadata { exif: Some(b"MM\0\x2a\0\0\0\x08\0\0".to_vec()), ..Metadata::default() };
+        assert!(matches!(embed(data, OutputFormat::WebP, &metadata), Err(MediaError::DecodeError(_))));
+    }
+}
@@ -1,192 +1,502 @@
 use std::collections::HashMap;
-use std::fs;
-use std::io::Cursor;
+use std::path::Path;
 
-use image::{DynamicImage, GenericImageView};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use image::imageops;
 use image::imageops::FilterType;
 use url::Url;
 
 use crate::error::MediaError;
-use crate::image_webp;
+use crate::{image_animation, image_cache, image_color, image_decode, image_decode_cache, image_limits, image_presets, image_sandbox};
+use crate::image_cache::CacheKey;
+use crate::image_color::ColorOptions;
+use crate::image_animation::Animation;
+use crate::image_encode::{EncodeOptions, OutputFormat};
+use crate::image_encode;
+use crate::image_style_args::{CropAnchor, ResizeMode, StyleArg, StyleOp};
+use crate::image_style_args;
+use crate::image_style_compat;
 
 /// 编辑图片 （切割、缩放）
-pub fn edit(image: Option<Vec<u8>>, query_params: &str) -> Result<Option<Vec<u8>>, MediaError> {
+pub fn edit(image: Option<&[u8]>, query_params: &str) -> Result<Option<Vec<u8>>, MediaError> {
+    Ok(edit_with_result(image, query_params)?.data)
+}
+
+/// 样式处理结果.
+pub struct EditResult {
+    /// 图片数据 (传入 to 时写入文件, 不返回数据).
+    pub data: Option<Vec<u8>>,
+
+    /// 目标大小模式 (max_bytes) 最终使用的质量.
+    pub quality: Option<f32>,
+
+    /// 输出宽度.
+    pub width: u32,
+
+    /// 输出高度.
+    pub height: u32,
+}
+
+/// 编辑图片 （切割、缩放）, 返回处理结果.
+pub fn edit_with_result(image: Option<&[u8]>, query_params: &str) -> Result<EditResult, MediaError> {
+    let query_params_map = query_map(query_params)?;
+    let options = EditOptions::new(&query_params_map)?;
+    let from = query_params_map.get("from");
+    let file;
+    let mut source_key = None;
+    let buffer = match image {
+        None => {
+            let path = from.ok_or(MediaError::InvalidParam("file not found".to_string()))?;
+            let (buffer, path, metadata) = image_sandbox::read_file_with_metadata(path)?;
+            if options.decode_cache {
+                source_key = image_decode_cache::file_key(&path, &metadata, buffer.len());
+            }
+            file = buffer;
+            file.as_slice()
+        }
+        Some(value) => value,
+    };
+
+    // 输出格式: type 优先, 其次按照 to 的扩展名, 默认 PNG
+    let format = match query_params_map.get("type") {
+        Some(value) => OutputFormat::parse(value)?,
+        None => match query_params_map.get("to") {
+            Some(output_path) => OutputFormat::from_path(output_path)?,
+            None => OutputFormat::Png,
+        }
+    };
+
+    // ICC 文件按照内容区分, 命中缓存时同样经过沙箱检查
+    let profile_key = ColorOptions::new(&query_params_map)?.profile_key()?;
+
+    // 相同的源图片内容和参数使用缓存结果 (参数 from、to 只有扩展名影响结果)
+    let result = if image_cache::enabled() {
+        let data = image_cache::cached(
+            || CacheKey::new("edit")
+                .params(&query_params_map, &["from", "to"])
+                .bytes(format.extension().as_bytes())
+                .optional(from.and_then(|path| Path::new(path).extension()).map(|value| value.as_encoded_bytes()))
+                .bytes(format!("{:?}", image_limits::limits()).as_bytes())
+                .optional(profile_key.as_deref().map(str::as_bytes))
+                .bytes(buffer),
+            || edit_buffer(buffer, source_key, profile_key.as_deref(), format, &query_params_map, &options).map(EditResult::into_cache),
+        )?;
+        EditResult::from_cache(data)?
+    } else {
+        edit_buffer(buffer, source_key, profile_key.as_deref(), format, &query_params_map, &options)?
+    };
+    write_result(result, query_params_map.get("to"))
+}
+
+/// 处理内存图片, 不写入文件; source_key 为文件来源的解码缓存键, 为空时按照内容计算, profile_key 为 ICC 文件内容的键.
+fn edit_buffer(buffer: &[u8], source_key: Option<String>, profile_key: Option<&str>, format: OutputFormat, query_params_map: &HashMap<String, String>, options: &EditOptions) -> Result<EditResult, MediaError> {
+    let from = query_params_map.get("from");
+    let args = match query_params_map.get("args") {
+        None => vec![],
+        Some(args) => image_style_args::parse(args, options.strict)?,
+    };
+
+    let mut encode_options = EncodeOptions::new(query_params_map)?;
+    encode_options.preserve(buffer, options.auto_orient);
+    let color = ColorOptions::new(query_params_map)?;
+    color.update_metadata(&mut encode_options.metadata)?;
+
+    // 动图输出为 WebP、GIF 时逐帧处理, 其他格式只保留第一帧
+    let animation = match format {
+        OutputFormat::WebP | OutputFormat::Gif => image_animation::decode(buffer)?,
+        _ => None,
+    };
+    match animation {
+        Some(animation) => {
+            let source = image_color::icc_profile(buffer);
+            let animation = animation.try_map(|image| apply_all(color.convert(image, source.as_deref())?, &args, options))?;
+            encode_animation(&animation, format, &encode_options)
+        }
+        None => {
+            // 解码结果还取决于扩展名 (无法从内容识别格式时)、auto_orient、icc 和资源限制
+            let image = image_decode_cache::decoded(options.decode_cache, || format!("{}|{:?}|{}|{:?}|{:?}|{:?}",
+                source_key.unwrap_or_else(|| image_decode_cache::bytes_key(buffer)),
+                from.and_then(|path| Path::new(path).extension()), options.auto_orient,
+                query_params_map.get("icc"), profile_key, image_limits::limits()),
+                || color.decode(buffer, from, options.auto_orient))?;
+            encode_image(apply_all(image, &args, options)?, format, &encode_options)
+        }
+    }
+}
+
+impl EditResult {
+    /// 缓存格式: 宽、高、质量 (无质量时为 NaN) 各 4 字节, 之后为图片数据.
+    fn into_cache(self) -> Vec<u8> {
+        let data = self.data.unwrap_or_default();
+        let mut buffer = Vec::with_capacity(data.len() + 12);
+        buffer.extend_from_slice(&self.width.to_le_bytes());
+        buffer.extend_from_slice(&self.height.to_le_bytes());
+        buffer.extend_from_slice(&self.quality.unwrap_or(f32::NAN).to_le_bytes());
+        buffer.extend_from_slice(&data);
+        buffer
+    }
+
+    fn from_cache(mut buffer: Vec<u8>) -> Result<EditResult, MediaError> {
+        if buffer.len() < 12 {
+            return Err(MediaError::Error("invalid cache entry".to_string()));
+        }
+        let field = |index: usize| [buffer[index], buffer[index + 1], buffer[index + 2], buffer[index + 3]];
+        let (width, height, quality) = (u32::from_le_bytes(field(0)), u32::from_le_bytes(field(4)), f32::from_le_bytes(field(8)));
+        buffer.drain(..12);
+        Ok(EditResult { data: Some(buffer), quality: Some(quality).filter(|value| !value.is_nan()), width, height })
+    }
+}
+
+/// 编码图片, 设置 max_bytes 时按照目标大小选择质量.
+pub fn encode_image(image: DynamicImage, format: OutputFormat, encode_options: &EncodeOptions) -> Result<EditResult, MediaError> {
+    let (data, quality, (width, height)) = match encode_options.max_bytes {
+        None => {
+            let dimensions = image.dimensions();
+            (image_encode::encode(image, format, encode_options)?, None, dimensions)
+        }
+        Some(max_bytes) => {
+            let result = image_encode::fit_size(format, max_bytes, |quality, scale| {
+                image_encode::encode(shrink(&image, scale), format, &encode_options.with_quality(quality))
+            })?;
+            (result.data, Some(result.quality), scaled_dimensions(image.width(), image.height(), result.scale))
+        }
+    };
+    Ok(EditResult { data: Some(data), quality, width, height })
+}
+
+/// 编码动图, 设置 max_bytes 时按照目标大小选择质量.
+pub fn encode_animation(animation: &Animation, format: OutputFormat, encode_options: &EncodeOptions) -> Result<EditResult, MediaError> {
+    let (data, quality, (width, height)) = match encode_options.max_bytes {
+        None => (image_animation::encode(animation, format, encode_options)?, None, animation.dimensions()),
+        Some(max_bytes) => {
+            let result = image_encode::fit_size(format, max_bytes, |quality, scale| {
+                let animation = animation.try_map_ref(|image| Ok(shrink(image, scale)))?;
+                image_animation::encode(&animation, format, &encode_options.with_quality(quality))
+            })?;
+            let (width, height) = animation.dimensions();
+            (result.data, Some(result.quality), scaled_dimensions(width, height, result.scale))
+        }
+    };
+    Ok(EditResult { data: Some(data), quality, width, height })
+}
+
+/// 传入 to 时写入文件, 不返回数据.
+pub fn write_result(mut result: EditResult, output_path: Option<&String>) -> Result<EditResult, MediaError> {
+    if let (Some(output_path), Some(data)) = (output_path, &result.data) {
+        image_sandbox::write_file(output_path, data)?;
+        result.data = None;
+    }
+    Ok(result)
+}
+
+/// 按比例缩小图片 (目标大小模式).
+fn shrink(image: &DynamicImage, scale: f32) -> DynamicImage {
+    if scale >= 1f32 {
+        return image.clone();
+    }
+    let (width, height) = scaled_dimensions(image.width(), image.height(), scale);
+    image.resize_exact(width, height, FilterType::CatmullRom)
+}
+
+/// 按比例计算宽高, 最小为 1.
+fn scaled_dimensions(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    if scale >= 1f32 {
+        return (width, height);
+    }
+    (((width as f32 * scale) as u32).max(1), ((height as f32 * scale) as u32).max(1))
+}
+
+/// 样式处理选项.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditOptions {
+    /// 严格模式, 无效参数直接返回错误.
+    pub strict: bool,
+
+    /// 背景色 (旋转空白区域、填充缩放等).
+    background: Rgba<u8>,
+
+    /// 按照 EXIF 方向矫正图片, 默认开启.
+    pub auto_orient: bool,
+
+    /// 裁剪超出图片时截断到图片范围, 而不是跳过.
+    clamp: bool,
+
+    /// 允许放大图片.
+    upscale: bool,
+
+    /// 使用解码缓存, 默认按照全局配置.
+    pub decode_cache: bool,
+}
+
+impl EditOptions {
+    /// 读取参数 strict、bg、auto_orient、clamp、upscale、decode_cache.
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<EditOptions, MediaError> {
+        Ok(EditOptions {
+            strict: query_bool(query_params_map, "strict", false)?,
+            background: match query_params_map.get("bg") {
+                None => Rgba([0, 0, 0, 0]),
+                Some(value) => parse_color(value)?,
+            },
+            auto_orient: query_bool(query_params_map, "auto_orient", true)?,
+            clamp: query_bool(query_params_map, "clamp", false)?,
+            upscale: query_bool(query_params_map, "upscale", false)?,
+            decode_cache: query_bool(query_params_map, "decode_cache", image_decode_cache::config().enabled)?,
+        })
+    }
+}
+
+/// 解析颜色, 支持 `rrggbb`、`rrggbbaa` (可带 # 前缀) 以及 `transparent`.
+pub fn parse_color(value: &str) -> Result<Rgba<u8>, MediaError> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("transparent") {
+        return Ok(Rgba([0, 0, 0, 0]));
+    }
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(MediaError::InvalidParam(format!("invalid color `{}`, expected rrggbb or rrggbbaa", value)));
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16);
+    let alpha = if hex.len() == 8 { channel(3)? } else { 255 };
+    Ok(Rgba([channel(0)?, channel(1)?, channel(2)?, alpha]))
+}
+
+/// 解析 `k=v&k=v` 格式的参数, 展开 style 引用的预设, 数据万象 imageMogr2、OSS x-oss-process 参数转换为 args 等参数.
+pub fn query_map(query_params: &str) -> Result<HashMap<String, String>, MediaError> {
     let url = format!("https://www.rust-lang.org?{}", query_params);
     let url = Url::parse(&url)?;
     let mut query_params_map = HashMap::new();
+    let mut order = Vec::new();
     for item in url.query_pairs() {
+        order.push(item.0.to_string());
         query_params_map.insert(item.0.to_string(),
                                 item.1.to_string());
     }
-    let mut image = match image {
-        None => {
-            let file_path = query_params_map.get("from")
-                .ok_or(MediaError::Error("file not found".to_string()))?;
-            image::open(file_path)?
+    image_presets::expand(&mut query_params_map)?;
+    image_style_compat::translate(&mut query_params_map, &order)?;
+    Ok(query_params_map)
+}
+
+/// 读取布尔参数, 支持 true/false/1/0, 未填写时返回默认值.
+pub fn query_bool(query_params_map: &HashMap<String, String>, key: &str, default: bool) -> Result<bool, MediaError> {
+    match query_params_map.get(key).map(|value| value.as_str()) {
+        None | Some("") => Ok(default),
+        Some("false") | Some("0") => Ok(false),
+        Some("true") | Some("1") => Ok(true),
+        Some(value) => Err(MediaError::InvalidParam(format!("{} must be true or false, got `{}`", key, value))),
+    }
+}
+
+/// 依次执行样式操作.
+pub fn apply_all(mut image: DynamicImage, args: &[StyleArg], options: &EditOptions) -> Result<DynamicImage, MediaError> {
+    for arg in args {
+        image = apply(image, arg, options)?;
+    }
+    Ok(image)
+}
+
+/// 执行单个样式操作, 图片不满足条件时 strict 模式返回错误, 否则原样返回.
+fn apply(image: DynamicImage, arg: &StyleArg, options: &EditOptions) -> Result<DynamicImage, MediaError> {
+    match arg.op {
+        StyleOp::ZoomWidth(value) => {
+            // 未允许放大时不放大图片
+            if image.width() < value && !options.upscale {
+                return Ok(image);
+            }
+            let scale = image.width() as f32 / value as f32;
+            let height = (image.height() as f32 / scale) as u32;
+            image_limits::limits().check_dimensions(value, height)?;
+            Ok(image.resize(value, height, FilterType::CatmullRom))
         }
-        Some(value) => {
-            image::load_from_memory(&value)?
+        StyleOp::ZoomHeight(value) => {
+            if image.height() < value && !options.upscale {
+                return Ok(image);
+            }
+            let scale = image.height() as f32 / value as f32;
+            let width = (image.width() as f32 / scale) as u32;
+            image_limits::limits().check_dimensions(width, value)?;
+            Ok(image.resize(width, value, FilterType::CatmullRom))
         }
-    };
-    let image = match &query_params_map.get("args") {
-        None => image,
-        Some(args) => {
-            let mut args = args.split("A").collect::<Vec<&str>>();
-            if args.len() > 5 {
-                args = args[0..5].to_owned();
+        StyleOp::ZoomScale(value) => {
+            if value > 10 && !options.upscale {
+                return Ok(image);
             }
-            for arg in args {
-                if arg.starts_with("Z") {
-                    let module = &arg[1..];
-                    if module.starts_with("W") {
-                        match module[1..].trim().parse::<u32>() {
-                            Ok(value) => {
-                                if image.width() < value {
-                                    continue;
-                                }
-                                let scale = image.width() as f32 / value as f32;
-                                let height = (image.height() as f32 / scale) as u32;
-                                image = image.resize(value, height, FilterType::CatmullRom);
-                            }
-                            Err(_) => continue
-                        }
-                    }
-                    if module.starts_with("H") {
-                        match module[1..].trim().parse::<u32>() {
-                            Ok(value) => {
-                                if image.height() < value {
-                                    continue;
-                                }
-                                let scale = image.height() as f32 / value as f32;
-                                let width = (image.width() as f32 / scale) as u32;
-                                image = image.resize(width, value, FilterType::CatmullRom);
-                            }
-                            Err(_) => continue
-                        }
-                    }
-                    if module.starts_with("S") {
-                        match module[1..].trim().parse::<u32>() {
-                            Ok(value) => {
-                                if value > 10 {
-                                    continue;
-                                }
-                                let width = (image.width() as f32 * (value as f32 / 10f32)) as u32;
-                                let height = (image.height() as f32 * (value as f32 / 10f32)) as u32;
-                                image = image.resize(width, height, FilterType::CatmullRom);
-                            }
-                            Err(_) => continue
-                        }
-                    }
-                    continue;
+            let width = (image.width() as f32 * (value as f32 / 10f32)) as u32;
+            let height = (image.height() as f32 * (value as f32 / 10f32)) as u32;
+            image_limits::limits().check_dimensions(width, height)?;
+            Ok(image.resize(width, height, FilterType::CatmullRom))
+        }
+        StyleOp::Resize { mode, width, height } => {
+            let width = width.resolve(image.width()).max(1);
+            let height = height.resolve(image.height()).max(1);
+            resize(image, mode, width, height, options)
+        }
+        StyleOp::Crop { anchor, width, height } => {
+            let mut width = width.resolve(image.width()).max(1);
+            let mut height = height.resolve(image.height()).max(1);
+            // 如果图片宽高不够
+            if width > image.width() || height > image.height() {
+                if options.clamp {
+                    width = width.min(image.width());
+                    height = height.min(image.height());
+                } else if options.strict {
+                    return Err(arg.token.error(format!("crop {}x{} exceeds image {}x{}",
+                                                       width, height, image.width(), image.height())));
+                } else {
+                    return Ok(image);
                 }
-                if arg.starts_with("C") && arg.len() > 2 {
-                    let module = &arg[1..];
-                    let values = module[2..].split("X").collect::<Vec<&str>>();
-                    let width = if values.len() > 0 {
-                        match values[0].trim().parse::<u32>() {
-                            Ok(value) => value,
-                            Err(_) => continue
-                        }
-                    } else {
-                        0
-                    };
-                    let height = if values.len() > 1 {
-                        match values[1].trim().parse::<u32>() {
-                            Ok(value) => value,
-                            Err(_) => continue
-                        }
-                    } else {
-                        width
-                    };
-                    // 如果宽高读取失败
-                    if width <= 0 || height <= 0 {
-                        continue;
-                    }
-                    // 如果图片宽高不够
-                    if width > image.width() || height > image.height() {
-                        continue;
-                    }
-                    if module.starts_with("LT") {
-                        let x = 0;
-                        let y = 0;
-                        image = image.crop(x, y, width, height);
-                    }
-                    if module.starts_with("RT") {
-                        let x = image.width() - width;
-                        let y = 0;
-                        image = image.crop(x, y, width, height);
-                    }
-                    if module.starts_with("LB") {
-                        let x = 0;
-                        let y = image.height() - height;
-                        image = image.crop(x, y, width, height);
-                    }
-                    if module.starts_with("RB") {
-                        let x = image.width() - width;
-                        let y = image.height() - height;
-                        image = image.crop(x, y, width, height);
-                    }
-                    if module.starts_with("LC") {
-                        let x = 0;
-                        let y = image.height() / 2 - height / 2;
-                        image = image.crop(x, y, width, height);
-                    }
-                    if module.starts_with("RC") {
-                        let x = image.width() - width;
-                        let y = image.height() / 2 - height / 2;
-                        image = image.crop(x, y, width, height);
-                    }
-                    if module.starts_with("CC") {
-                        let x = image.width() / 2 - width / 2;
-                        let y = image.height() / 2 - height / 2;
-                        image = image.crop(x, y, width, height);
-                    }
-                    continue;
+            }
+            let (x, y) = match anchor {
+                CropAnchor::LeftTop => (0, 0),
+                CropAnchor::RightTop => (image.width() - width, 0),
+                CropAnchor::LeftBottom => (0, image.height() - height),
+                CropAnchor::RightBottom => (image.width() - width, image.height() - height),
+                CropAnchor::LeftCenter => (0, image.height() / 2 - height / 2),
+                CropAnchor::RightCenter => (image.width() - width, image.height() / 2 - height / 2),
+                CropAnchor::Center => (image.width() / 2 - width / 2, image.height() / 2 - height / 2),
+                CropAnchor::TopCenter => (image.width() / 2 - width / 2, 0),
+                CropAnchor::BottomCenter => (image.width() / 2 - width / 2, image.height() - height),
+            };
+            Ok(image.crop_imm(x, y, width, height))
+        }
+        StyleOp::CropOffset { x, y, width, height } => {
+            let x = x.resolve(image.width());
+            let y = y.resolve(image.height());
+            let mut width = width.resolve(image.width()).max(1);
+            let mut height = height.resolve(image.height()).max(1);
+            // 起点在图片之外无法裁剪, 超出部分按照 clamp 截断
+            let outside = x >= image.width() || y >= image.height();
+            let oversized = x as u64 + width as u64 > image.width() as u64
+                || y as u64 + height as u64 > image.height() as u64;
+            if outside || (oversized && !options.clamp) {
+                if options.strict {
+                    return Err(arg.token.error(format!("crop {}x{} at {},{} exceeds image {}x{}",
+                                                       width, height, x, y, image.width(), image.height())));
                 }
+                return Ok(image);
             }
-            image
+            width = width.min(image.width() - x);
+            height = height.min(image.height() - y);
+            Ok(image.crop_imm(x, y, width, height))
         }
-    };
+        StyleOp::Rotate(0) => Ok(image),
+        StyleOp::Rotate(90) => Ok(image.rotate90()),
+        StyleOp::Rotate(180) => Ok(image.rotate180()),
+        StyleOp::Rotate(270) => Ok(image.rotate270()),
+        StyleOp::Rotate(degrees) => rotate(&image, degrees, options.background),
+        StyleOp::FlipHorizontal => Ok(image.fliph()),
+        StyleOp::FlipVertical => Ok(image.flipv()),
+        StyleOp::Blur(0) => Ok(image),
+        StyleOp::Blur(sigma) => Ok(image.blur(sigma as f32)),
+    }
+}
 
-    match &query_params_map.get("type") {
-        None => {
-            match query_params_map.get("to") {
-                None => {
-                    let mut buffer = Cursor::new(Vec::new());
-                    image.write_to(&mut buffer, image::ImageOutputFormat::Png)?;
-                    Ok(Some(buffer.into_inner()))
-                }
-                Some(output_path) => {
-                    image.save(output_path.to_string())?;
-                    Ok(None)
-                }
+/// 按照宽高框缩放, 未允许放大时缩放比例不超过 1.
+fn resize(image: DynamicImage, mode: ResizeMode, width: u32, height: u32, options: &EditOptions) -> Result<DynamicImage, MediaError> {
+    let scale_x = width as f64 / image.width() as f64;
+    let scale_y = height as f64 / image.height() as f64;
+    let limit = |scale: f64| if options.upscale { scale } else { scale.min(1.0) };
+    let scaled = |scale: f64| (
+        ((image.width() as f64 * scale).round() as u32).max(1),
+        ((image.height() as f64 * scale).round() as u32).max(1),
+    );
+    let limits = image_limits::limits();
+    match mode {
+        ResizeMode::Fit => {
+            let (target_width, target_height) = scaled(limit(scale_x.min(scale_y)));
+            if (target_width, target_height) == image.dimensions() {
+                return Ok(image);
             }
+            limits.check_dimensions(target_width, target_height)?;
+            Ok(image.resize_exact(target_width, target_height, FilterType::CatmullRom))
         }
-        Some(_) => {
-            let result = &image_webp::encoded_webp(image, match &query_params_map.get("quality") {
-                None => { 75f32 }
-                Some(value) => {
-                    value.parse::<f32>()?
-                }
-            })?.to_owned();
-            match query_params_map.get("to") {
-                None => {
-                    Ok(Some(result.to_vec()))
-                }
-                Some(output_path) => {
-                    fs::write(output_path, result)?;
-                    Ok(None)
-                }
+        ResizeMode::Cover => {
+            let (target_width, target_height) = scaled(limit(scale_x.max(scale_y)));
+            limits.check_dimensions(target_width, target_height)?;
+            let image = image.resize_exact(target_width, target_height, FilterType::CatmullRom);
+            let (crop_width, crop_height) = (width.min(target_width), height.min(target_height));
+            Ok(image.crop_imm((target_width - crop_width) / 2, (target_height - crop_height) / 2, crop_width, crop_height))
+        }
+        ResizeMode::Min => {
+            let (target_width, target_height) = scaled(limit(scale_x.max(scale_y)));
+            if (target_width, target_height) == image.dimensions() {
+                return Ok(image);
             }
+            limits.check_dimensions(target_width, target_height)?;
+            Ok(image.resize_exact(target_width, target_height, FilterType::CatmullRom))
+        }
+        ResizeMode::Stretch => {
+            let (target_width, target_height) = if options.upscale {
+                (width, height)
+            } else {
+                (width.min(image.width()), height.min(image.height()))
+            };
+            limits.check_dimensions(target_width, target_height)?;
+            Ok(image.resize_exact(target_width, target_height, FilterType::CatmullRom))
+        }
+        ResizeMode::Pad => {
+            limits.check_dimensions(width, height)?;
+            let (target_width, target_height) = scaled(limit(scale_x.min(scale_y)));
+            let image = image.resize_exact(target_width.min(width), target_height.min(height), FilterType::CatmullRom);
+            let mut canvas = RgbaImage::from_pixel(width, height, options.background);
+            imageops::overlay(&mut canvas, &image.to_rgba8(),
+                              ((width - image.width()) / 2) as i64, ((height - image.height()) / 2) as i64);
+            Ok(DynamicImage::ImageRgba8(canvas))
         }
     }
 }
 
-/// 获取图片元数据.
-pub fn metadata(image: DynamicImage) -> HashMap<&'static str, String> {
+/// 任意角度顺时针旋转, 画布扩展到能容纳旋转后的图片, 空白区域使用 fill 填充.
+fn rotate(image: &DynamicImage, degrees: u32, fill: Rgba<u8>) -> Result<DynamicImage, MediaError> {
+    let source = image.to_rgba8();
+    let (sin, cos) = (degrees as f64).to_radians().sin_cos();
+    let (source_width, source_height) = (source.width() as f64, source.height() as f64);
+    let width = (source_width * cos.abs() + source_height * sin.abs()).round().max(1.0) as u32;
+    let height = (source_width * sin.abs() + source_height * cos.abs()).round().max(1.0) as u32;
+    image_limits::limits().check_dimensions(width, height)?;
+
+    // 目标像素中心反向旋转回源图坐标, 双线性插值取色
+    let mut target = RgbaImage::from_pixel(width, height, fill);
+    for (x, y, pixel) in target.enumerate_pixels_mut() {
+        let dx = x as f64 + 0.5 - width as f64 / 2.0;
+        let dy = y as f64 + 0.5 - height as f64 / 2.0;
+        let source_x = dx * cos + dy * sin + source_width / 2.0 - 0.5;
+        let source_y = -dx * sin + dy * cos + source_height / 2.0 - 0.5;
+        *pixel = sample(&source, source_x, source_y, fill);
+    }
+    Ok(DynamicImage::ImageRgba8(target))
+}
+
+/// 双线性插值采样, 超出源图的部分视为 fill.
+fn sample(source: &RgbaImage, x: f64, y: f64, fill: Rgba<u8>) -> Rgba<u8> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let neighbours = [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (1, 0, fx * (1.0 - fy)),
+        (0, 1, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ];
+    let mut result = [0f64; 4];
+    for (offset_x, offset_y, weight) in neighbours {
+        let (px, py) = (x0 as i64 + offset_x, y0 as i64 + offset_y);
+        let pixel = if px >= 0 && py >= 0 && px < source.width() as i64 && py < source.height() as i64 {
+            *source.get_pixel(px as u32, py as u32)
+        } else {
+            fill
+        };
+        for (channel, value) in result.iter_mut().enumerate() {
+            *value += pixel[channel] as f64 * weight;
+        }
+    }
+    Rgba(result.map(|value| value.round().clamp(0.0, 255.0) as u8))
+}
+
+/// 获取图片元数据, image 为未矫正的原始图片, orientation 为 EXIF 方向.
+pub fn metadata(image: DynamicImage, orientation: Option<u32>) -> HashMap<&'static str, String> {
     let mut result = HashMap::new();
-    // 获取图像分辨率
-    let (width, height) = image.dimensions();
+    // 获取图像分辨率 (原始及按照 EXIF 方向矫正后)
+    let (raw_width, raw_height) = image.dimensions();
+    let (width, height) = image_decode::oriented_dimensions(raw_width, raw_height, orientation);
 
     // 获取图像的颜色类型和位深度
     let color_type = image.color();
@@ -194,7 +504,104 @@ pub fn metadata(image: DynamicImage) -> HashMap<&'static str, String> {
 
     result.insert("width", width.to_string());
     result.insert("height", height.to_string());
+    result.insert("rawWidth", raw_width.to_string());
+    result.insert("rawHeight", raw_height.to_string());
+    result.insert("orientation", orientation.unwrap_or(1).to_string());
     result.insert("colorType", format!("{:?}", color_type));
-    return result;
+    result
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    /// 左半边红色、右半边蓝色的图片.
+    fn source(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, _| if x < width / 2 { RED } else { BLUE }))
+    }
+
+    fn styled(image: DynamicImage, query_params: &str) -> Result<DynamicImage, MediaError> {
+        let query_params_map = query_map(query_params)?;
+        let options = EditOptions::new(&query_params_map)?;
+        let args = image_style_args::parse(&query_params_map["args"], options.strict)?;
+        apply_all(image, &args, &options)
+    }
+
+    fn dimensions(image: DynamicImage, query_params: &str) -> (u32, u32) {
+        styled(image, query_params).unwrap().dimensions()
+    }
+
+    #[test]
+    fn resize_modes() {
+        assert_eq!(dimensions(source(400, 200), "args=ZF100X100"), (100, 50));
+        assert_eq!(dimensions(source(400, 200), "args=ZC100X100"), (100, 100));
+        assert_eq!(dimensions(source(400, 200), "args=ZM100X100"), (200, 100));
+        assert_eq!(dimensions(source(400, 200), "args=ZE100X100"), (100, 100));
+
+        // 未允许放大时不超过原图
+        assert_eq!(dimensions(source(400, 200), "args=ZF800X800"), (400, 200));
+        assert_eq!(dimensions(source(400, 200), "args=ZF800X800&upscale=true"), (800, 400));
+        assert_eq!(dimensions(source(400, 200), "args=ZE800X100"), (400, 100));
+        assert_eq!(dimensions(source(400, 200), "args=ZE800X100&upscale=true"), (800, 100));
+    }
+
+    #[test]
+    fn cover_crops_centre() {
+        let image = styled(source(400, 200), "args=ZC100X100").unwrap().to_rgba8();
+        // 缩放为 200x100 后居中裁剪, 左右两边分别来自红色、蓝色部分
+        assert_eq!(*image.get_pixel(5, 50), RED);
+        assert_eq!(*image.get_pixel(94, 50), BLUE);
+    }
+
+    #[test]
+    fn pad_centres_with_background() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(400, 200, GREEN));
+        let image = styled(image, "args=ZP100X100&bg=ff0000").unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (100, 100));
+        assert_eq!(*image.get_pixel(0, 0), RED);
+        assert_eq!(*image.get_pixel(50, 10), RED);
+        assert_eq!(*image.get_pixel(50, 50), GREEN);
+        assert_eq!(*image.get_pixel(50, 89), RED);
+
+        let image = styled(source(4, 2), "args=ZP8X8&bg=transparent").unwrap().to_rgba8();
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+    }
+
+    #[test]
+    fn crop_offset_clamp() {
+        // 超出图片的裁剪默认跳过, clamp 时截断, strict 时报错
+        assert_eq!(dimensions(source(400, 200), "args=CO300X150X200X100"), (400, 200));
+        assert_eq!(dimensions(source(400, 200), "args=CO300X150X200X100&clamp=true"), (100, 50));
+        assert!(styled(source(400, 200), "args=CO300X150X200X100&strict=true").is_err());
+        assert!(styled(source(400, 200), "args=CO400X0X10X10&clamp=true&strict=true").is_err());
+
+        let image = styled(source(400, 200), "args=CO200X0X50PX50P").unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (200, 100));
+        assert!(image.pixels().all(|pixel| *pixel == BLUE));
+        assert_eq!(dimensions(source(400, 200), "args=CBC50P"), (200, 100));
+    }
+
+    #[test]
+    fn rotate_and_flip() {
+        let image = styled(source(400, 200), "args=R90").unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (200, 400));
+        assert_eq!(*image.get_pixel(0, 0), RED);
+        assert_eq!(*image.get_pixel(0, 399), BLUE);
+
+        let image = styled(source(400, 200), "args=FH").unwrap().to_rgba8();
+        assert_eq!(*image.get_pixel(0, 0), BLUE);
+
+        // 任意角度扩展画布, 空白区域使用 bg 填充
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, GREEN));
+        let image = styled(image, "args=R45&bg=ff0000").unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (141, 141));
+        assert_eq!(*image.get_pixel(0, 0), RED);
+        assert_eq!(*image.get_pixel(140, 140), RED);
+        assert_eq!(*image.get_pixel(70, 70), GREEN);
+        assert_eq!(*image.get_pixel(70, 3), GREEN);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::error::MediaError;
+use crate::image_limits;
+
+/// 单次处理默认最多支持的样式操作数量 (见 image_limits).
+pub const MAX_ARGS: usize = 5;
+
+/// 样式参数分隔符.
+pub const SEPARATOR: char = 'A';
+
+/// 模糊最大 sigma.
+pub const MAX_BLUR: u32 = 50;
+
+/// 裁剪锚点.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CropAnchor {
+    /// LT 左上角.
+    LeftTop,
+    /// RT 右上角.
+    RightTop,
+    /// LB 左下角.
+    LeftBottom,
+    /// RB 右下角.
+    RightBottom,
+    /// LC 左居中.
+    LeftCenter,
+    /// RC 右居中.
+    RightCenter,
+    /// CC 完全居中.
+    Center,
+    /// TC 上居中.
+    TopCenter,
+    /// BC 下居中.
+    BottomCenter,
+}
+
+/// 按照宽高框缩放的模式.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// ZF 等比缩放到宽高框以内.
+    Fit,
+    /// ZC 等比缩放到覆盖宽高框, 然后居中裁剪.
+    Cover,
+    /// ZE 拉伸到指定宽高.
+    Stretch,
+    /// ZP 等比缩放到宽高框以内, 然后使用背景色填充到指定宽高.
+    Pad,
+    /// ZM 等比缩放到覆盖宽高框, 不裁剪.
+    Min,
+}
+
+/// 尺寸, 像素或者当前图片的百分比 (数值后缀 P).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Pixel(u32),
+    Percent(u32),
+}
+
+impl Dimension {
+    /// 按照当前图片的边长换算为像素.
+    pub fn resolve(self, total: u32) -> u32 {
+        match self {
+            Dimension::Pixel(value) => value,
+            Dimension::Percent(value) => (total as u64 * value as u64 / 100) as u32,
+        }
+    }
+}
+
+/// 样式操作.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StyleOp {
+    /// ZW 按照宽度等比缩放.
+    ZoomWidth(u32),
+    /// ZH 按照高度等比缩放.
+    ZoomHeight(u32),
+    /// ZS 按照十分比缩放.
+    ZoomScale(u32),
+    /// ZF/ZC/ZE/ZP 按照宽高框缩放.
+    Resize { mode: ResizeMode, width: Dimension, height: Dimension },
+    /// C 按照锚点裁剪.
+    Crop { anchor: CropAnchor, width: Dimension, height: Dimension },
+    /// CO 按照坐标裁剪.
+    CropOffset { x: Dimension, y: Dimension, width: Dimension, height: Dimension },
+    /// R 顺时针旋转角度 (0-359), 非直角时空白区域使用背景色填充.
+    Rotate(u32),
+    /// FH 水平翻转.
+    FlipHorizontal,
+    /// FV 垂直翻转.
+    FlipVertical,
+    /// B 高斯模糊 (sigma 0-50, 0 不处理).
+    Blur(u32),
+}
+
+/// 样式参数片段 (原始文本及其在参数中的位置).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleToken {
+    pub text: String,
+    pub position: usize,
+}
+
+/// 解析后的样式操作.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleArg {
+    pub op: StyleOp,
+    pub token: StyleToken,
+}
+
+impl StyleToken {
+    /// 基于当前片段创建错误.
+    pub fn error(&self, message: impl Into<String>) -> MediaError {
+        MediaError::StyleError {
+            token: self.text.clone(),
+            position: self.position,
+            message: message.into(),
+        }
+    }
+}
+
+/// 按照分隔符拆分样式参数, 记录每个片段的起始位置.
+pub fn tokenize(args: &str) -> Vec<StyleToken> {
+    let mut result = Vec::new();
+    let mut position = 0;
+    for text in args.split(SEPARATOR) {
+        let offset = text.len() - text.trim_start().len();
+        result.push(StyleToken {
+            text: text.trim().to_string(),
+            position: position + offset,
+        });
+        position += text.len() + SEPARATOR.len_utf8();
+    }
+    result
+}
+
+/// 解析样式参数, strict 模式下遇到无效片段直接返回错误, 否则跳过.
+pub fn parse(args: &str, strict: bool) -> Result<Vec<StyleArg>, MediaError> {
+    let mut result = Vec::new();
+    if args.trim().is_empty() {
+        return Ok(result);
+    }
+    for token in tokenize(args) {
+        match parse_op(&token) {
+            Ok(op) => result.push(StyleArg { op, token }),
+            Err(error) if strict => return Err(error),
+            Err(_) => continue,
+        }
+    }
+    let max_ops = image_limits::limits().max_ops;
+    if result.len() > max_ops {
+        if strict {
+            return Err(result[max_ops].token.error(format!("too many ops, at most {}", max_ops)));
+        }
+        result.truncate(max_ops);
+    }
+    Ok(result)
+}
+
+/// 解析单个样式片段.
+fn parse_op(token: &StyleToken) -> Result<StyleOp, MediaError> {
+    let text = token.text.as_str();
+    if text.is_empty() {
+        return Err(token.error("empty op"));
+    }
+    if let Some(module) = text.strip_prefix('Z') {
+        if let Some(value) = module.strip_prefix('W') {
+            return Ok(StyleOp::ZoomWidth(parse_size(token, value)?));
+        }
+        if let Some(value) = module.strip_prefix('H') {
+            return Ok(StyleOp::ZoomHeight(parse_size(token, value)?));
+        }
+        if let Some(value) = module.strip_prefix('S') {
+            return Ok(StyleOp::ZoomScale(parse_size(token, value)?));
+        }
+        let mode = match module.get(0..1) {
+            Some("F") => ResizeMode::Fit,
+            Some("C") => ResizeMode::Cover,
+            Some("E") => ResizeMode::Stretch,
+            Some("P") => ResizeMode::Pad,
+            Some("M") => ResizeMode::Min,
+            _ => return Err(token.error("unknown zoom mode, expected ZW, ZH, ZS, ZF, ZC, ZE, ZP or ZM")),
+        };
+        let (width, height) = parse_dimensions(token, &module[1..])?;
+        return Ok(StyleOp::Resize { mode, width, height });
+    }
+    if let Some(module) = text.strip_prefix('C') {
+        if let Some(value) = module.strip_prefix('O') {
+            let values = value.split(['X', 'x']).collect::<Vec<&str>>();
+            if values.len() != 4 {
+                return Err(token.error("offset crop expects CO<x>X<y>X<width>X<height>"));
+            }
+            return Ok(StyleOp::CropOffset {
+                x: parse_dimension(token, values[0], true)?,
+                y: parse_dimension(token, values[1], true)?,
+                width: parse_dimension(token, values[2], false)?,
+                height: parse_dimension(token, values[3], false)?,
+            });
+        }
+        let anchor = match module.get(0..2) {
+            Some("LT") => CropAnchor::LeftTop,
+            Some("RT") => CropAnchor::RightTop,
+            Some("LB") => CropAnchor::LeftBottom,
+            Some("RB") => CropAnchor::RightBottom,
+            Some("LC") => CropAnchor::LeftCenter,
+            Some("RC") => CropAnchor::RightCenter,
+            Some("CC") => CropAnchor::Center,
+            Some("TC") => CropAnchor::TopCenter,
+            Some("BC") => CropAnchor::BottomCenter,
+            _ => return Err(token.error("unknown crop anchor, expected LT, RT, LB, RB, LC, RC, CC, TC, BC or O")),
+        };
+        let (width, height) = parse_dimensions(token, &module[2..])?;
+        return Ok(StyleOp::Crop { anchor, width, height });
+    }
+    if let Some(value) = text.strip_prefix('R') {
+        let degrees = value.trim().parse::<i32>()
+            .map_err(|error| token.error(format!("invalid angle `{}`: {}", value, error)))?;
+        return Ok(StyleOp::Rotate(degrees.rem_euclid(360) as u32));
+    }
+    if let Some(module) = text.strip_prefix('F') {
+        return match module {
+            "H" => Ok(StyleOp::FlipHorizontal),
+            "V" => Ok(StyleOp::FlipVertical),
+            _ => Err(token.error("unknown flip mode, expected FH or FV")),
+        };
+    }
+    if let Some(value) = text.strip_prefix('B') {
+        let sigma = value.trim().parse::<u32>()
+            .map_err(|error| token.error(format!("invalid sigma `{}`: {}", value, error)))?;
+        if sigma > MAX_BLUR {
+            return Err(token.error(format!("blur sigma must be between 0 and {}", MAX_BLUR)));
+        }
+        return Ok(StyleOp::Blur(sigma));
+    }
+    Err(token.error("unknown op"))
+}
+
+/// 解析 `宽[X高]`, 高不填默认与宽相同.
+fn parse_dimensions(token: &StyleToken, value: &str) -> Result<(Dimension, Dimension), MediaError> {
+    match value.split_once(['X', 'x']) {
+        None => {
+            let width = parse_dimension(token, value, false)?;
+            Ok((width, width))
+        }
+        Some((width, height)) => Ok((parse_dimension(token, width, false)?, parse_dimension(token, height, false)?)),
+    }
+}
+
+/// 解析像素或百分比 (后缀 P), allow_zero 为 false 时必须大于 0.
+fn parse_dimension(token: &StyleToken, value: &str, allow_zero: bool) -> Result<Dimension, MediaError> {
+    let value = value.trim();
+    let (number, percent) = match value.strip_suffix('P') {
+        Some(number) => (number, true),
+        None => (value, false),
+    };
+    let number = if allow_zero {
+        number.parse::<u32>().map_err(|error| token.error(format!("invalid number `{}`: {}", value, error)))?
+    } else {
+        parse_size(token, number)?
+    };
+    Ok(if percent { Dimension::Percent(number) } else { Dimension::Pixel(number) })
+}
+
+/// 解析大于 0 的尺寸.
+fn parse_size(token: &StyleToken, value: &str) -> Result<u32, MediaError> {
+    let size = value.trim().parse::<u32>()
+        .map_err(|error| token.error(format!("invalid number `{}`: {}", value, error)))?;
+    if size == 0 {
+        return Err(token.error("size must be greater than 0"));
+    }
+    Ok(size)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_zoom_and_crop() {
+        let args = parse("ZW800ACLT450x300ACCC300", true).unwrap();
+        let ops: Vec<StyleOp> = args.into_iter().map(|arg| arg.op).collect();
+        assert_eq!(ops, vec![
+            StyleOp::ZoomWidth(800),
+            StyleOp::Crop { anchor: CropAnchor::LeftTop, width: Dimension::Pixel(450), height: Dimension::Pixel(300) },
+            StyleOp::Crop { anchor: CropAnchor::Center, width: Dimension::Pixel(300), height: Dimension::Pixel(300) },
+        ]);
+    }
+
+    #[test]
+    fn parse_offset_and_percent_crop() {
+        let args = parse("CO10X0X50PX200ACTC50P", true).unwrap();
+        let ops: Vec<StyleOp> = args.into_iter().map(|arg| arg.op).collect();
+        assert_eq!(ops, vec![
+            StyleOp::CropOffset {
+                x: Dimension::Pixel(10),
+                y: Dimension::Pixel(0),
+                width: Dimension::Percent(50),
+                height: Dimension::Pixel(200),
+            },
+            StyleOp::Crop { anchor: CropAnchor::TopCenter, width: Dimension::Percent(50), height: Dimension::Percent(50) },
+        ]);
+        assert!(parse("CO10X10X0X10", true).is_err());
+        assert_eq!(Dimension::Percent(50).resolve(301), 150);
+    }
+
+    #[test]
+    fn parse_resize_modes() {
+        let args = parse("ZF300X200AZP100", true).unwrap();
+        let ops: Vec<StyleOp> = args.into_iter().map(|arg| arg.op).collect();
+        assert_eq!(ops, vec![
+            StyleOp::Resize { mode: ResizeMode::Fit, width: Dimension::Pixel(300), height: Dimension::Pixel(200) },
+            StyleOp::Resize { mode: ResizeMode::Pad, width: Dimension::Pixel(100), height: Dimension::Pixel(100) },
+        ]);
+        assert!(parse("ZQ100", true).is_err());
+    }
+
+    #[test]
+    fn parse_rotate_and_flip() {
+        let args = parse("R90AR-90AR45AFHAFV", true).unwrap();
+        let ops: Vec<StyleOp> = args.into_iter().map(|arg| arg.op).collect();
+        assert_eq!(ops, vec![
+            StyleOp::Rotate(90),
+            StyleOp::Rotate(270),
+            StyleOp::Rotate(45),
+            StyleOp::FlipHorizontal,
+            StyleOp::FlipVertical,
+        ]);
+        assert!(parse("FX", true).is_err());
+    }
+
+    #[test]
+    fn parse_blur_and_min_resize() {
+        let args = parse("ZM300X200AB8", true).unwrap();
+        let ops: Vec<StyleOp> = args.into_iter().map(|arg| arg.op).collect();
+        assert_eq!(ops, vec![
+            StyleOp::Resize { mode: ResizeMode::Min, width: Dimension::Pixel(300), height: Dimension::Pixel(200) },
+            StyleOp::Blur(8),
+        ]);
+        assert!(parse("B51", true).is_err());
+    }
+
+    #[test]
+    fn strict_reports_token_and_position() {
+        match parse("ZW800AZWx800", true) {
+            Err(MediaError::StyleError { token, position, .. }) => {
+                assert_eq!(token, "ZWx800");
+                assert_eq!(position, 6);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lenient_skips_invalid_ops() {
+        let args = parse("ZWx800AQ1AZH600", false).unwrap();
+        assert_eq!(args.len(), 1);
+        assert_eq!(args[0].op, StyleOp::ZoomHeight(600));
+        assert_eq!(args[0].token.position, 10);
+    }
+
+    #[test]
+    fn strict_rejects_too_many_ops() {
+        assert!(parse("ZW1AZW1AZW1AZW1AZW1AZW1", true).is_err());
+        assert_eq!(parse("ZW1AZW1AZW1AZW1AZW1AZW1", false).unwrap().len(), MAX_ARGS);
+    }
+}
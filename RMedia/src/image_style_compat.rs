@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+
+use crate::error::MediaError;
+use crate::image_encode::OutputFormat;
+use crate::image_style;
+use crate::image_style_args::{StyleToken, MAX_BLUR, SEPARATOR};
+
+/// 腾讯云数据万象参数前缀, 例如 `imageMogr2/thumbnail/300x/format/webp`.
+const IMAGE_MOGR2: &str = "imageMogr2";
+
+/// 阿里云 OSS 参数名, 例如 `x-oss-process=image/resize,w_300/format,webp`.
+const OSS_PROCESS: &str = "x-oss-process";
+
+/// 转换结果.
+#[derive(Debug, Default)]
+struct Translated {
+    /// 样式操作 (样式参数片段).
+    args: Vec<String>,
+
+    /// 编码及处理参数 (type、quality、interlace、strip 等).
+    params: Vec<(&'static str, String)>,
+
+    /// 包含裁剪操作, 云服务裁剪超出图片时截断到图片范围.
+    clamp: bool,
+}
+
+/// 将 imageMogr2、x-oss-process 参数转换为样式参数 args 以及 type、quality、interlace、strip 等参数.
+///
+/// 多个 imageMogr2 参数按照 order (参数在请求中的顺序) 处理, 不在 order 中的按照名称排序后追加.
+/// 样式操作追加在已有 args 之后, 转换后删除原参数; strict 模式下遇到不支持的操作返回错误, 否则跳过.
+pub fn translate(query_params_map: &mut HashMap<String, String>, order: &[String]) -> Result<(), MediaError> {
+    let is_image_mogr2 = |key: &String| key.as_str() == IMAGE_MOGR2 || key.starts_with("imageMogr2/");
+    let mut keys: Vec<String> = Vec::new();
+    for key in order.iter().filter(|key| is_image_mogr2(key) && query_params_map.contains_key(*key)) {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+    let mut rest = query_params_map.keys()
+        .filter(|key| is_image_mogr2(key) && !keys.contains(key))
+        .cloned()
+        .collect::<Vec<String>>();
+    rest.sort();
+    keys.extend(rest);
+    let process = query_params_map.remove(OSS_PROCESS);
+    if keys.is_empty() && process.is_none() {
+        return Ok(());
+    }
+    let strict = image_style::query_bool(query_params_map, "strict", false)?;
+    let mut translated = Translated::default();
+    for key in keys {
+        query_params_map.remove(&key);
+        image_mogr2(&key, strict, &mut translated)?;
+    }
+    if let Some(value) = process {
+        oss_process(&value, strict, &mut translated)?;
+    }
+
+    if !translated.args.is_empty() {
+        let mut args = query_params_map.remove("args").filter(|value| !value.trim().is_empty())
+            .into_iter()
+            .collect::<Vec<String>>();
+        args.extend(translated.args);
+        query_params_map.insert("args".to_string(), args.join(&SEPARATOR.to_string()));
+    }
+    for (key, value) in translated.params {
+        query_params_map.insert(key.to_string(), value);
+    }
+    if translated.clamp {
+        query_params_map.entry("clamp".to_string()).or_insert_with(|| "true".to_string());
+    }
+    Ok(())
+}
+
+/// 解析数据万象参数, 支持 thumbnail、crop (gravity、dx、dy)、cut、rotate、flip、format、quality、interlace、strip、blur、auto-orient,
+/// 多个处理使用 `|` 连接. rotate 取值 0 ~ 360, 360 与 0 相同不旋转; blur 的 σ 不超过半径.
+fn image_mogr2(value: &str, strict: bool, translated: &mut Translated) -> Result<(), MediaError> {
+    for pipeline in split(value, '|', 0) {
+        let tokens = split(&pipeline.text, '/', pipeline.position);
+        if tokens[0].text != IMAGE_MOGR2 {
+            skip(strict, tokens[0].error("unsupported process, expected imageMogr2"))?;
+            continue;
+        }
+        let mut modifiers: HashMap<String, StyleToken> = HashMap::new();
+        let mut index = 1;
+        while index < tokens.len() {
+            let name = &tokens[index];
+            index += 1;
+            let result = match name.text.as_str() {
+                "" => continue,
+                "strip" => {
+                    translated.params.push(("strip", "all".to_string()));
+                    Ok(())
+                }
+                "auto-orient" => {
+                    translated.params.push(("auto_orient", "true".to_string()));
+                    Ok(())
+                }
+                _ => match tokens.get(index) {
+                    None => Err(name.error("missing value")),
+                    Some(value) => {
+                        index += 1;
+                        match name.text.as_str() {
+                            "gravity" | "dx" | "dy" => {
+                                modifiers.insert(name.text.clone(), value.clone());
+                                Ok(())
+                            }
+                            "crop" => {
+                                // 裁剪之后的 gravity、dx、dy
+                                while let (Some(key), Some(modifier)) = (tokens.get(index), tokens.get(index + 1)) {
+                                    if !matches!(key.text.as_str(), "gravity" | "dx" | "dy") {
+                                        break;
+                                    }
+                                    modifiers.insert(key.text.clone(), modifier.clone());
+                                    index += 2;
+                                }
+                                let result = image_mogr2_crop(value, &modifiers, translated);
+                                modifiers.clear();
+                                result
+                            }
+                            _ => image_mogr2_op(name, value, translated),
+                        }
+                    }
+                }
+            };
+            if let Err(error) = result {
+                skip(strict, error)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 数据万象单个操作.
+fn image_mogr2_op(name: &StyleToken, value: &StyleToken, translated: &mut Translated) -> Result<(), MediaError> {
+    match name.text.as_str() {
+        "thumbnail" => translated.args.push(thumbnail(value)?),
+        "cut" => {
+            let values = value.text.split('x').collect::<Vec<&str>>();
+            if values.len() != 4 {
+                return Err(value.error("cut expects <width>x<height>x<dx>x<dy>"));
+            }
+            translated.args.push(format!("CO{}X{}X{}X{}", number(value, values[2], 0, u32::MAX)?, number(value, values[3], 0, u32::MAX)?,
+                                         number(value, values[0], 1, u32::MAX)?, number(value, values[1], 1, u32::MAX)?));
+            translated.clamp = true;
+        }
+        // 360 按照 0 处理 (不旋转)
+        "rotate" => translated.args.push(format!("R{}", number(value, &value.text, 0, 360)? % 360)),
+        "flip" => translated.args.push(match value.text.as_str() {
+            "horizontal" => "FH".to_string(),
+            "vertical" => "FV".to_string(),
+            _ => return Err(value.error("flip expects horizontal or vertical")),
+        }),
+        "format" => translated.params.push(("type", format(value)?)),
+        "quality" => translated.params.push(("quality", number(value, value.text.trim_end_matches('!'), 0, 100)?.to_string())),
+        "interlace" => translated.params.push(("interlace", flag(value)?)),
+        "blur" => {
+            let (radius, sigma) = value.text.split_once('x').ok_or_else(|| value.error("blur expects <radius>x<sigma>"))?;
+            translated.args.push(blur(number(value, radius, 1, 50)?, number(value, sigma, 0, MAX_BLUR)?));
+        }
+        _ => return Err(name.error("unsupported imageMogr2 operation")),
+    }
+    Ok(())
+}
+
+/// 数据万象缩放: `!50p`、`!50px`、`!x50p`、`<W>x`、`x<H>`、`<W>x<H>`、`<W>x<H>!`、`<W>x<H>>`、`!<W>x<H>r`.
+fn thumbnail(token: &StyleToken) -> Result<String, MediaError> {
+    let value = token.text.as_str();
+    if let Some(scale) = value.strip_prefix('!') {
+        if let Some(percent) = scale.strip_suffix("px") {
+            return Ok(format!("ZE{}PX100P", number(token, percent, 1, 1000)?));
+        }
+        if let Some(percent) = scale.strip_prefix('x').and_then(|value| value.strip_suffix('p')) {
+            return Ok(format!("ZE100PX{}P", number(token, percent, 1, 1000)?));
+        }
+        if let Some(percent) = scale.strip_suffix('p') {
+            let percent = number(token, percent, 1, 1000)?;
+            return Ok(format!("ZE{}PX{}P", percent, percent));
+        }
+        if let Some(size) = scale.strip_suffix('r') {
+            let (width, height) = both(token, size_pair(token, size)?)?;
+            return Ok(format!("ZM{}X{}", width, height));
+        }
+        return Err(token.error("unsupported thumbnail mode"));
+    }
+    match value.chars().last() {
+        Some('!') => {
+            let (width, height) = both(token, size_pair(token, &value[..value.len() - 1])?)?;
+            Ok(format!("ZE{}X{}", width, height))
+        }
+        Some('<' | '@') => Err(token.error("unsupported thumbnail mode")),
+        _ => match size_pair(token, value.trim_end_matches('>'))? {
+            (Some(width), Some(height)) => Ok(format!("ZF{}X{}", width, height)),
+            (Some(width), None) => Ok(format!("ZW{}", width)),
+            (None, Some(height)) => Ok(format!("ZH{}", height)),
+            (None, None) => Err(token.error("thumbnail expects <width>x<height>")),
+        },
+    }
+}
+
+/// 数据万象裁剪: `<W>x<H>`、`<W>x`、`x<H>`, 按照 gravity 锚点 (默认 northwest), dx、dy 偏移只支持 northwest.
+fn image_mogr2_crop(value: &StyleToken, modifiers: &HashMap<String, StyleToken>, translated: &mut Translated) -> Result<(), MediaError> {
+    let (width, height) = size_pair(value, &value.text)?;
+    let anchor = match modifiers.get("gravity") {
+        None => "LT",
+        Some(gravity) => anchor(gravity, &gravity.text)?,
+    };
+    let offset = |key: &str| modifiers.get(key).map(|token| number(token, &token.text, 0, u32::MAX)).transpose();
+    let (x, y) = (offset("dx")?, offset("dy")?);
+    translated.args.push(crop(value, anchor, width, height, x, y)?);
+    translated.clamp = true;
+    Ok(())
+}
+
+/// 解析 OSS 参数, 支持 resize、crop、rotate、format、quality、interlace、blur、auto-orient.
+/// rotate 取值 0 ~ 360, 360 与 0 相同不旋转; blur 的 σ 不超过半径.
+fn oss_process(value: &str, strict: bool, translated: &mut Translated) -> Result<(), MediaError> {
+    let tokens = split(value, '/', 0);
+    if tokens[0].text != "image" {
+        return skip(strict, tokens[0].error("unsupported process, expected image"));
+    }
+    for token in tokens.iter().skip(1).filter(|token| !token.text.is_empty()) {
+        if let Err(error) = oss_action(token, translated) {
+            skip(strict, error)?;
+        }
+    }
+    Ok(())
+}
+
+/// OSS 单个操作, 例如 `resize,w_300,h_200,m_fill`.
+fn oss_action(token: &StyleToken, translated: &mut Translated) -> Result<(), MediaError> {
+    let mut parts = token.text.split(',');
+    let action = parts.next().unwrap_or_default();
+    let parts = parts.collect::<Vec<&str>>();
+    let value = || parts.first().copied().ok_or_else(|| token.error("missing value"));
+    let params = || -> Result<HashMap<&str, &str>, MediaError> {
+        parts.iter().map(|part| part.split_once('_').ok_or_else(|| token.error(format!("invalid parameter `{}`", part)))).collect()
+    };
+    match action {
+        "resize" => {
+            let params = params()?;
+            if params.contains_key("l") || params.contains_key("s") {
+                return Err(token.error("resize by longest or shortest side is not supported"));
+            }
+            if params.get("limit") == Some(&"0") {
+                translated.params.push(("upscale", "true".to_string()));
+            }
+            if let Some(color) = params.get("color") {
+                translated.params.push(("bg", color.to_string()));
+            }
+            let size = |key: &str| params.get(key).map(|value| number(token, value, 1, u32::MAX)).transpose();
+            if let Some(percent) = params.get("p") {
+                let percent = number(token, percent, 1, 1000)?;
+                translated.args.push(format!("ZE{}PX{}P", percent, percent));
+                return Ok(());
+            }
+            let mode = match params.get("m").copied().unwrap_or("lfit") {
+                "lfit" => 'F',
+                "mfit" => 'M',
+                "fill" => 'C',
+                "pad" => 'P',
+                "fixed" => 'E',
+                mode => return Err(token.error(format!("unsupported resize mode `{}`", mode))),
+            };
+            translated.args.push(match (size("w")?, size("h")?) {
+                (Some(width), Some(height)) => format!("Z{}{}X{}", mode, width, height),
+                (Some(width), None) => format!("ZW{}", width),
+                (None, Some(height)) => format!("ZH{}", height),
+                (None, None) => return Err(token.error("resize expects w, h or p")),
+            });
+        }
+        "crop" => {
+            let params = params()?;
+            let size = |key: &str| params.get(key).map(|value| number(token, value, 1, u32::MAX)).transpose();
+            let offset = |key: &str| params.get(key).map(|value| number(token, value, 0, u32::MAX)).transpose();
+            let anchor = match params.get("g") {
+                None => "LT",
+                Some(gravity) => anchor(token, gravity)?,
+            };
+            translated.args.push(crop(token, anchor, size("w")?, size("h")?, offset("x")?, offset("y")?)?);
+            translated.clamp = true;
+        }
+        // 360 按照 0 处理 (不旋转)
+        "rotate" => translated.args.push(format!("R{}", number(token, value()?, 0, 360)? % 360)),
+        "format" => translated.params.push(("type", format(&StyleToken { text: value()?.to_string(), position: token.position })?)),
+        "quality" => {
+            let params = params()?;
+            let quality = params.get("Q").or_else(|| params.get("q")).ok_or_else(|| token.error("quality expects q or Q"))?;
+            translated.params.push(("quality", number(token, quality, 1, 100)?.to_string()));
+        }
+        "interlace" => translated.params.push(("interlace", flag(&StyleToken { text: value()?.to_string(), position: token.position })?)),
+        "blur" => {
+            let params = params()?;
+            let value = |key: &str| params.get(key).ok_or_else(|| token.error(format!("blur expects {}", key)));
+            translated.args.push(blur(number(token, value("r")?, 1, 50)?, number(token, value("s")?, 1, MAX_BLUR)?));
+        }
+        "auto-orient" => translated.params.push(("auto_orient", flag(&StyleToken { text: value()?.to_string(), position: token.position })?)),
+        _ => return Err(token.error("unsupported x-oss-process operation")),
+    }
+    Ok(())
+}
+
+/// 按照锚点或坐标裁剪, 未指定的宽高为图片宽高, 坐标只支持左上角锚点.
+fn crop(token: &StyleToken, anchor: &str, width: Option<u32>, height: Option<u32>, x: Option<u32>, y: Option<u32>) -> Result<String, MediaError> {
+    let size = |value: Option<u32>| value.map(|value| value.to_string()).unwrap_or_else(|| "100P".to_string());
+    if x.is_none() && y.is_none() {
+        return Ok(format!("C{}{}X{}", anchor, size(width), size(height)));
+    }
+    if anchor != "LT" {
+        return Err(token.error("crop offset only supports northwest gravity"));
+    }
+    Ok(format!("CO{}X{}X{}X{}", x.unwrap_or(0), y.unwrap_or(0), size(width), size(height)))
+}
+
+/// 九宫格方位 (数据万象、OSS 两种写法) 转换为裁剪锚点.
+fn anchor(token: &StyleToken, value: &str) -> Result<&'static str, MediaError> {
+    match value {
+        "northwest" | "nw" => Ok("LT"),
+        "north" => Ok("TC"),
+        "northeast" | "ne" => Ok("RT"),
+        "west" => Ok("LC"),
+        "center" => Ok("CC"),
+        "east" => Ok("RC"),
+        "southwest" | "sw" => Ok("LB"),
+        "south" => Ok("BC"),
+        "southeast" | "se" => Ok("RB"),
+        _ => Err(token.error(format!("unknown gravity `{}`", value))),
+    }
+}
+
+/// 解析 `<W>x<H>`, 宽高都可以省略.
+fn size_pair(token: &StyleToken, value: &str) -> Result<(Option<u32>, Option<u32>), MediaError> {
+    let (width, height) = value.split_once('x').ok_or_else(|| token.error("expected <width>x<height>"))?;
+    let size = |value: &str| if value.is_empty() { Ok(None) } else { number(token, value, 1, u32::MAX).map(Some) };
+    Ok((size(width)?, size(height)?))
+}
+
+fn both(token: &StyleToken, size: (Option<u32>, Option<u32>)) -> Result<(u32, u32), MediaError> {
+    match size {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(token.error("both width and height are required")),
+    }
+}
+
+fn format(token: &StyleToken) -> Result<String, MediaError> {
+    let format = OutputFormat::parse(&token.text).map_err(|_| token.error(format!("unsupported format `{}`", token.text)))?;
+    Ok(format.extension().to_string())
+}
+
+fn flag(token: &StyleToken) -> Result<String, MediaError> {
+    match token.text.as_str() {
+        "0" => Ok("false".to_string()),
+        "1" => Ok("true".to_string()),
+        _ => Err(token.error("expected 0 or 1")),
+    }
+}
+
+/// 模糊操作: 高斯模糊只有 σ 一个参数, 半径限制模糊范围, 因此 σ 不超过半径.
+fn blur(radius: u32, sigma: u32) -> String {
+    format!("B{}", sigma.min(radius))
+}
+
+fn number(token: &StyleToken, value: &str, min: u32, max: u32) -> Result<u32, MediaError> {
+    let number = value.trim().parse::<u32>()
+        .map_err(|error| token.error(format!("invalid number `{}`: {}", value, error)))?;
+    if number < min || number > max {
+        return Err(token.error(format!("{} must be between {} and {}", number, min, max)));
+    }
+    Ok(number)
+}
+
+/// 按照分隔符拆分, 记录每个片段在原参数中的位置.
+fn split(value: &str, separator: char, offset: usize) -> Vec<StyleToken> {
+    let mut position = offset;
+    value.split(separator).map(|text| {
+        let token = StyleToken { text: text.to_string(), position };
+        position += text.len() + separator.len_utf8();
+        token
+    }).collect()
+}
+
+/// strict 模式返回错误, 否则跳过.
+fn skip(strict: bool, error: MediaError) -> Result<(), MediaError> {
+    if strict {
+        return Err(error);
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translated(query_params: &str) -> HashMap<String, String> {
+        image_style::query_map(query_params).unwrap()
+    }
+
+    #[test]
+    fn translate_image_mogr2() {
+        let map = translated("imageMogr2/thumbnail/300x/crop/200x100/gravity/center/rotate/90/format/webp/quality/80/interlace/1/strip/blur/8x5");
+        assert_eq!(map["args"], "ZW300ACCC200X100AR90AB5");
+        assert_eq!(map["type"], "webp");
+        assert_eq!(map["quality"], "80");
+        assert_eq!(map["interlace"], "true");
+        assert_eq!(map["strip"], "all");
+        assert_eq!(map["clamp"], "true");
+        assert!(!map.keys().any(|key| key.starts_with(IMAGE_MOGR2)));
+
+        assert_eq!(translated("imageMogr2/thumbnail/!50p")["args"], "ZE50PX50P");
+        assert_eq!(translated("imageMogr2/thumbnail/!300x200r")["args"], "ZM300X200");
+        assert_eq!(translated("imageMogr2/crop/300x/dx/10/dy/20")["args"], "CO10X20X300X100P");
+        assert_eq!(translated("imageMogr2/rotate/360")["args"], "R0");
+        assert_eq!(translated("imageMogr2/blur/3x10")["args"], "B3");
+    }
+
+    #[test]
+    fn translate_image_mogr2_in_query_order() {
+        assert_eq!(translated("imageMogr2/thumbnail/300x&imageMogr2/rotate/90")["args"], "ZW300AR90");
+        assert_eq!(translated("imageMogr2/rotate/90&imageMogr2/thumbnail/300x")["args"], "R90AZW300");
+    }
+
+    #[test]
+    fn translate_oss_process() {
+        let map = translated("args=FH&x-oss-process=image/resize,m_fill,w_300,h_200/crop,w_100,h_100,g_se/rotate,90/format,jpg/quality,q_80/interlace,1/blur,r_3,s_2");
+        assert_eq!(map["args"], "FHAZC300X200ACRB100X100AR90AB2");
+        assert_eq!(map["type"], "jpg");
+        assert_eq!(map["quality"], "80");
+        assert_eq!(map["interlace"], "true");
+        assert!(!map.contains_key(OSS_PROCESS));
+
+        assert_eq!(translated("x-oss-process=image/resize,w_300")["args"], "ZW300");
+        assert_eq!(translated("x-oss-process=image/rotate,360")["args"], "R0");
+        assert_eq!(translated("x-oss-process=image/blur,r_2,s_8")["args"], "B2");
+        assert_eq!(translated("x-oss-process=image/resize,p_50,limit_0")["upscale"], "true");
+    }
+
+    #[test]
+    fn strict_reports_unsupported_operation() {
+        match image_style::query_map("strict=true&x-oss-process=image/resize,w_300/watermark,text_aGk") {
+            Err(MediaError::StyleError { token, position, .. }) => {
+                assert_eq!(token, "watermark,text_aGk");
+                assert_eq!(position, 19);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(translated("x-oss-process=image/resize,w_300/watermark,text_aGk")["args"], "ZW300");
+        assert!(image_style::query_map("strict=1&imageMogr2/thumbnail/100x100@").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::MediaError;
+use crate::image_encode::OutputFormat;
+use crate::image_handle::ImageHandle;
+use crate::image_style::{EditOptions, EditResult};
+use crate::image_style_args::{StyleArg, StyleOp};
+use crate::{image_sandbox, image_style, image_style_args};
+
+/// 单个输出.
+struct Variant {
+    /// 公共参数和输出参数合并后的参数.
+    query_params_map: HashMap<String, String>,
+
+    options: EditOptions,
+
+    args: Vec<StyleArg>,
+
+    /// 用于比较的样式操作.
+    ops: Vec<StyleOp>,
+}
+
+impl Variant {
+    fn new(base: &HashMap<String, String>, spec: &str) -> Result<Variant, MediaError> {
+        let spec = image_style::query_map(spec)?;
+        if spec.contains_key("strip") {
+            return Err(MediaError::InvalidParam("strip must be set in common params".to_string()));
+        }
+        let mut query_params_map = base.clone();
+        query_params_map.extend(spec);
+        let options = EditOptions::new(&query_params_map)?;
+        let args = match query_params_map.get("args") {
+            None => vec![],
+            Some(args) => image_style_args::parse(args, options.strict)?,
+        };
+        let ops = args.iter().map(|arg| arg.op.clone()).collect();
+        if let Some(template) = query_params_map.get("to") {
+            check_template(template)?;
+        }
+        Ok(Variant { query_params_map, options, args, ops })
+    }
+
+    /// 与另一个输出共用前 length 个样式操作.
+    fn shares(&self, other: &Variant, length: usize) -> bool {
+        self.options == other.options && other.ops.len() >= length && self.ops[..length] == other.ops[..length]
+    }
+}
+
+/// 一次解码生成多个输出, 相同的样式操作前缀只处理一次.
+///
+/// query_params 为公共参数 (from、auto_orient、icc、strip 以及所有输出的默认参数), specs 为每个输出的参数 (args、type、quality、to 等, 不能指定 strip),
+/// to 支持 {index}、{width}、{height}、{type} 占位符 (至少包含一个).
+pub fn variants(image: Option<&[u8]>, query_params: &str, specs: &[String]) -> Result<Vec<EditResult>, MediaError> {
+    let base = image_style::query_map(query_params)?;
+    let variants = specs.iter().map(|spec| Variant::new(&base, spec)).collect::<Result<Vec<Variant>, MediaError>>()?;
+
+    // 解码一次
+    let from = base.get("from");
+    let file;
+    let buffer = match image {
+        None => {
+            file = image_sandbox::read_file(from.ok_or(MediaError::InvalidParam("file not found".to_string()))?)?;
+            file.as_slice()
+        }
+        Some(value) => value,
+    };
+    let source = Arc::new(ImageHandle::open(buffer, from.map(|value| value.as_str()), &base)?);
+
+    // 多个输出共用的中间结果
+    let mut shared: Vec<(usize, usize, Arc<ImageHandle>)> = Vec::new();
+    let mut results = Vec::with_capacity(variants.len());
+    for (index, variant) in variants.iter().enumerate() {
+        let (mut length, mut image) = shared.iter()
+            .filter(|(owner, length, _)| variants[*owner].shares(variant, *length))
+            .max_by_key(|(_, length, _)| *length)
+            .map(|(_, length, image)| (*length, image.clone()))
+            .unwrap_or((0, source.clone()));
+        while length < variant.args.len() {
+            image = Arc::new(image.style_args(&variant.args[length..length + 1], &variant.options)?);
+            length += 1;
+            if variants[index + 1..].iter().any(|other| variant.shares(other, length)) {
+                shared.push((index, length, image.clone()));
+            }
+        }
+        results.push(encode(&image, variant, index)?);
+        // 后续输出不再共用的中间结果及时释放
+        shared.retain(|(owner, length, _)| variants[index + 1..].iter().any(|other| variants[*owner].shares(other, *length)));
+    }
+    Ok(results)
+}
+
+/// 编码输出, 按照模板写入文件.
+fn encode(image: &ImageHandle, variant: &Variant, index: usize) -> Result<EditResult, MediaError> {
+    let mut query_params_map = variant.query_params_map.clone();
+    let template = query_params_map.remove("to");
+    // strip 在解码时已经确定
+    query_params_map.remove("strip");
+    let format = match (query_params_map.get("type"), &template) {
+        (Some(value), _) => OutputFormat::parse(value)?,
+        (None, Some(template)) => OutputFormat::from_path(template)?,
+        (None, None) => OutputFormat::Png,
+    };
+    query_params_map.insert("type".to_string(), format.extension().to_string());
+    let result = image.encode(&query_params_map)?;
+    let output_path = template.map(|template| output_path(&template, index, result.width, result.height, format));
+    image_style::write_result(result, output_path.as_ref())
+}
+
+const PLACEHOLDERS: [&str; 4] = ["{index}", "{width}", "{height}", "{type}"];
+
+/// 输出路径模板至少包含一个占位符, 否则多个输出写入同一个文件.
+fn check_template(template: &str) -> Result<(), MediaError> {
+    if !PLACEHOLDERS.iter().any(|placeholder| template.contains(placeholder)) {
+        return Err(MediaError::InvalidParam(format!("to `{}` has no placeholder ({})", template, PLACEHOLDERS.join("、"))));
+    }
+    Ok(())
+}
+
+/// 替换输出路径模板的占位符.
+fn output_path(template: &str, index: usize, width: u32, height: u32, format: OutputFormat) -> String {
+    template
+        .replace("{index}", &index.to_string())
+        .replace("{width}", &width.to_string())
+        .replace("{height}", &height.to_string())
+        .replace("{type}", format.extension())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageOutputFormat};
+
+    use super::*;
+
+    #[test]
+    fn share_style_prefix() {
+        let base = HashMap::new();
+        let (first, second) = (Variant::new(&base, "args=ZW200AR90").unwrap(), Variant::new(&base, "args=ZW200AR180&type=webp").unwrap());
+        assert!(first.shares(&second, 1) && !first.shares(&second, 2));
+        let strict = Variant::new(&base, "args=ZW200&strict=true").unwrap();
+        assert!(!first.shares(&strict, 1));
+
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(400, 100).write_to(&mut png, ImageOutputFormat::Png).unwrap();
+        let specs = ["args=ZW200AR90", "args=ZW200", "args=ZW100"].map(String::from);
+        let results = variants(Some(png.get_ref()), "", &specs).unwrap();
+        let sizes = results.iter().map(|result| (result.width, result.height)).collect::<Vec<_>>();
+        assert_eq!(sizes, [(50, 200), (200, 50), (100, 25)]);
+    }
+
+    #[test]
+    fn expand_to_template() {
+        assert_eq!(output_path("/out/{index}_{width}x{height}.{type}", 2, 200, 50, OutputFormat::WebP), "/out/2_200x50.webp");
+        assert!(check_template("/out/{index}.png").is_ok());
+        assert!(matches!(check_template("/out/thumb.png"), Err(MediaError::InvalidParam(_))));
+        assert!(matches!(Variant::new(&HashMap::new(), "to=/out/thumb.png"), Err(MediaError::InvalidParam(_))));
+        assert!(matches!(Variant::new(&HashMap::new(), "strip=keep-all"), Err(MediaError::InvalidParam(_))));
+    }
+}
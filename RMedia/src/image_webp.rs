@@ -1,17 +1,189 @@
+use std::collections::HashMap;
+use std::path::Path;
+
 use image::DynamicImage;
-use webp::{Decoder, Encoder, WebPImage, WebPMemory};
+use webp::{BitstreamFeatures, Decoder, Encoder, WebPConfig, WebPImage, WebPMemory};
 
 use crate::error::MediaError;
+use crate::{image_animation, image_color, image_limits, image_strip, image_style};
+use crate::image_color::ColorOptions;
+use crate::image_encode::OutputFormat;
+use crate::image_strip::StripPolicy;
+
+/// WebP 编码配置, 未填写的参数使用 libwebp 默认值.
+#[derive(Debug, Clone)]
+pub struct WebPOptions {
+    /// 图片质量 0-100, 无损模式下表示压缩力度.
+    pub quality: f32,
+
+    /// 无损编码.
+    pub lossless: bool,
+
+    /// 近无损预处理级别 0-100 (100 表示关闭), 仅无损模式有效.
+    pub near_lossless: Option<u8>,
+
+    /// 压缩方法 0-6, 越大越慢压缩率越高.
+    pub method: Option<u8>,
+
+    /// 透明通道质量 0-100.
+    pub alpha_quality: Option<u8>,
+
+    /// 空间噪声整形强度 0-100.
+    pub sns_strength: Option<u8>,
+
+    /// 去块滤波强度 0-100.
+    pub filter_strength: Option<u8>,
+
+    /// 目标 PSNR (dB), 优先于 quality.
+    pub target_psnr: Option<f32>,
+
+    /// 目标大小 (字节), 优先于 quality.
+    pub target_size: Option<u32>,
+
+    /// 多线程编码.
+    pub multithreading: bool,
+}
 
-/// 编码WebP 图形, image 传入图形文件, quality 输出图片质量 0-100 之间.
-pub fn encoded_webp(image: DynamicImage, quality: f32) -> Result<WebPMemory, MediaError> {
+impl Default for WebPOptions {
+    fn default() -> Self {
+        WebPOptions {
+            quality: 75f32,
+            lossless: false,
+            near_lossless: None,
+            method: None,
+            alpha_quality: None,
+            sns_strength: None,
+            filter_strength: None,
+            target_psnr: None,
+            target_size: None,
+            multithreading: false,
+        }
+    }
+}
+
+impl WebPOptions {
+    /// 读取参数 quality、lossless、near_lossless、method、alpha_quality、sns_strength、filter_strength、
+    /// target_psnr、target_size、multithreading.
+    pub fn new(query_params_map: &HashMap<String, String>) -> Result<WebPOptions, MediaError> {
+        let range = |key: &str, max: u8| -> Result<Option<u8>, MediaError> {
+            match query_params_map.get(key) {
+                None => Ok(None),
+                Some(value) => match value.parse::<u8>()? {
+                    value if value <= max => Ok(Some(value)),
+                    value => Err(MediaError::InvalidParam(format!("{} must be between 0 and {}, got {}", key, max, value))),
+                }
+            }
+        };
+        Ok(WebPOptions {
+            quality: match query_params_map.get("quality") {
+                None => 75f32,
+                Some(value) => value.parse::<f32>()?.clamp(0f32, 100f32),
+            },
+            lossless: image_style::query_bool(query_params_map, "lossless", false)?,
+            near_lossless: range("near_lossless", 100)?,
+            method: range("method", 6)?,
+            alpha_quality: range("alpha_quality", 100)?,
+            sns_strength: range("sns_strength", 100)?,
+            filter_strength: range("filter_strength", 100)?,
+            target_psnr: match query_params_map.get("target_psnr") {
+                None => None,
+                Some(value) => Some(value.parse::<f32>()?),
+            },
+            target_size: match query_params_map.get("target_size") {
+                None => None,
+                Some(value) => Some(value.parse::<u32>()?),
+            },
+            multithreading: image_style::query_bool(query_params_map, "multithreading", false)?,
+        })
+    }
+
+    /// 转换为 libwebp 配置.
+    pub fn config(&self) -> Result<WebPConfig, MediaError> {
+        let mut config = WebPConfig::new().map_err(|_| MediaError::Error("webp config init fail".to_string()))?;
+        config.quality = self.quality;
+        config.lossless = self.lossless as i32;
+        config.thread_level = self.multithreading as i32;
+        if let Some(value) = self.near_lossless {
+            config.near_lossless = value as i32;
+        }
+        if let Some(value) = self.method {
+            config.method = value as i32;
+        }
+        if let Some(value) = self.alpha_quality {
+            config.alpha_quality = value as i32;
+        }
+        if let Some(value) = self.sns_strength {
+            config.sns_strength = value as i32;
+        }
+        if let Some(value) = self.filter_strength {
+            config.filter_strength = value as i32;
+        }
+        if let Some(value) = self.target_psnr {
+            config.target_PSNR = value;
+        }
+        if let Some(value) = self.target_size {
+            config.target_size = value as i32;
+        }
+        Ok(config)
+    }
+}
+
+/// 编码WebP 图形, image 传入图形文件 (灰度、16 位图片先转换为 8 位 RGB/RGBA), options 编码配置.
+pub fn encoded_webp_with(image: DynamicImage, options: &WebPOptions) -> Result<WebPMemory, MediaError> {
+    // WebP 只支持 8 位 RGB/RGBA
+    let image = match image {
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => image,
+        image if image.color().has_alpha() => DynamicImage::ImageRgba8(image.to_rgba8()),
+        image => DynamicImage::ImageRgb8(image.to_rgb8()),
+    };
     let encoder: Encoder = Encoder::from_image(&image)?;
-    Ok(encoder.encode(quality))
+    encoder.encode_advanced(&options.config()?)
+        .map_err(|error| MediaError::Error(format!("webp encode fail: {:?}", error)))
+}
+
+/// 编码WebP 图形, buffer 传入内存图形文件, 动图 (GIF、WebP) 保留所有帧, path 为文件来源 (用于识别格式),
+/// strip 为元数据保留策略, color 为色彩管理选项.
+pub fn encoded_webp_from<P: AsRef<Path>>(buffer: &[u8], path: Option<P>, options: &WebPOptions, strip: StripPolicy,
+                                         color: &ColorOptions) -> Result<Vec<u8>, MediaError> {
+    let data = match image_animation::decode(buffer)? {
+        Some(animation) => {
+            let source = image_color::icc_profile(buffer);
+            let animation = animation.try_map(|image| color.convert(image, source.as_deref()))?;
+            image_animation::encode_webp(&animation, options)?
+        }
+        None => encoded_webp_with(color.decode(buffer, path, true)?, options)?.to_vec(),
+    };
+    let mut metadata = image_strip::extract(buffer, strip, true);
+    color.update_metadata(&mut metadata)?;
+    image_strip::embed(data, OutputFormat::WebP, &metadata)
 }
 
-/// 解码WebP 图形, image 传入内存图形文件, quality 输出图片质量 0-100 之间.
+/// 解码WebP 图形, image 传入内存图形文件, 动图返回第一帧.
 pub fn decode_webp(image: Vec<u8>) -> Result<DynamicImage, MediaError> {
+    if let Some(animation) = image_animation::decode(&image)? {
+        return animation.frames.into_iter().next()
+            .map(|frame| frame.image)
+            .ok_or(MediaError::DecodeError("webp decode fail".to_string()));
+    }
+    let limits = image_limits::limits();
+    limits.check_bytes(image.len() as u64)?;
+    let features = BitstreamFeatures::new(&image).ok_or(MediaError::DecodeError("webp decode fail".to_string()))?;
+    limits.check_dimensions(features.width(), features.height())?;
     let decoder: Decoder = Decoder::new(&image);
-    let webp: WebPImage = decoder.decode().ok_or(MediaError::Error("decoder fail".to_string()))?;
+    let webp: WebPImage = decoder.decode().ok_or(MediaError::DecodeError("webp decode fail".to_string()))?;
     Ok(webp.to_image())
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_gray_and_16_bit() {
+        let options = WebPOptions::default();
+        assert!(encoded_webp_with(DynamicImage::new_luma8(4, 4), &options).is_ok());
+        assert!(encoded_webp_with(DynamicImage::new_luma_a8(4, 4), &options).is_ok());
+        assert!(encoded_webp_with(DynamicImage::new_rgba16(4, 4), &options).is_ok());
+    }
+}
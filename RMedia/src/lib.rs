@@ -1,32 +1,114 @@
-use std::fs;
 use std::io::Cursor;
-use std::ptr::null_mut;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::{self, null_mut};
+use std::slice;
 
 use jni::JNIEnv;
-use jni::objects::{JByteArray, JClass, JString};
-use jni::sys::{jbyteArray, jfloat, jstring};
+use jni::objects::{JByteArray, JByteBuffer, JClass, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jbyteArray, jfloat, jintArray, jlong, jlongArray, jobject, jobjectArray, jstring};
 
 use crate::error::MediaError;
+use crate::image_encode::{EncodeOptions, OutputFormat};
+use crate::image_color::ColorOptions;
+use crate::image_strip::StripPolicy;
+
+pub mod image_webp;
+pub mod error;
+pub mod image_qr_code;
+pub mod image_style;
+pub mod image_style_args;
+pub mod image_style_compat;
+pub mod image_decode;
+pub mod image_encode;
+pub mod image_animation;
+pub mod image_metadata;
+pub mod image_strip;
+pub mod image_color;
+pub mod image_limits;
+pub mod image_sandbox;
+pub mod image_handle;
+pub mod image_variants;
+pub mod image_presets;
+pub mod image_cache;
+pub mod image_decode_cache;
+
+
+/// 执行 JNI 调用, 错误和 panic 转换为 Java 异常后返回 default, panic 不会穿过 JNI 边界.
+fn guard<'local, T>(env: &mut JNIEnv<'local>, operation: &str, default: T,
+                    action: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, MediaError>) -> T {
+    let error = match panic::catch_unwind(AssertUnwindSafe(|| action(env))) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(error)) => error,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|value| value.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            MediaError::Panic(format!("{} panicked: {}", operation, message))
+        }
+    };
+    throw(env, error);
+    default
+}
 
-mod image_webp;
-mod error;
-mod image_qr_code;
-mod image_style;
-
+/// 抛出错误对应的 Java 异常 (com.media.image.MediaException 及其子类).
+fn throw(env: &mut JNIEnv, error: MediaError) {
+    // JVM 已经有待处理的异常 (例如读取参数失败) 时保留原异常
+    if env.exception_check().unwrap_or(false) {
+        return;
+    }
+    let message = error.to_string();
+    if env.throw_new(error.exception_class(), &message).is_err() {
+        // 异常类不存在时退回 RuntimeException
+        let _ = env.exception_clear();
+        let _ = env.throw_new("java/lang/RuntimeException", &message);
+    }
+}
 
 /// 读取JVM 内存的数组.
 fn get_vec(env: &JNIEnv, from: JByteArray) -> Result<Option<Vec<u8>>, MediaError> {
     if from.is_null() {
         return Ok(None);
     }
-    let array_length = if from.is_null() { 0 } else { env.get_array_length(&from)? };
-    if array_length > 0 {
-        let mut buffer: Vec<i8> = vec![0; array_length as usize];
-        env.get_byte_array_region(from, 0, &mut buffer)?;
-        Ok(Some(buffer.iter().map(|&i| i as u8).collect()))
-    } else {
-        Ok(None)
+    let buffer = env.convert_byte_array(from)?;
+    Ok(if buffer.is_empty() { None } else { Some(buffer) })
+}
+
+/// 读取 direct ByteBuffer 中 position 到 limit 之间的数据, 不复制内存.
+fn get_buffer<'a>(env: &mut JNIEnv, from: &'a JByteBuffer) -> Result<Option<&'a [u8]>, MediaError> {
+    if from.is_null() {
+        return Ok(None);
     }
+    let address = env.get_direct_buffer_address(from)
+        .map_err(|_| MediaError::InvalidParam("direct ByteBuffer required".to_string()))?;
+    let position = env.call_method(from, "position", "()I", &[])?.i()? as usize;
+    let limit = env.call_method(from, "limit", "()I", &[])?.i()? as usize;
+    if position >= limit {
+        return Ok(None);
+    }
+    // 调用期间 Java 持有 ByteBuffer 的引用, 内存不会释放
+    Ok(Some(unsafe { slice::from_raw_parts(address.add(position), limit - position) }))
+}
+
+/// 将结果包装为 MediaBuffer (direct ByteBuffer), 内存由 Rust 持有, 通过 MediaBuffer.close 或 Cleaner 释放.
+fn new_media_buffer(env: &mut JNIEnv, data: Vec<u8>) -> Result<jobject, MediaError> {
+    let length = data.len();
+    let address = Box::into_raw(data.into_boxed_slice()) as *mut u8;
+    let mut create = || -> Result<jobject, MediaError> {
+        let buffer = unsafe { env.new_direct_byte_buffer(address, length)? };
+        let object = env.new_object("com/media/image/MediaBuffer", "(Ljava/nio/ByteBuffer;JJ)V",
+                                    &[JValue::Object(&buffer), JValue::Long(address as i64), JValue::Long(length as i64)])?;
+        Ok(object.into_raw())
+    };
+    let result = create();
+    if result.is_err() {
+        free_buffer(address, length);
+    }
+    result
+}
+
+/// 释放 new_media_buffer 分配的内存.
+fn free_buffer(address: *mut u8, length: usize) {
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(address, length)) });
 }
 
 /// 读取JVM 内存的字符串.
@@ -34,38 +116,107 @@ fn get_string(env: &mut JNIEnv, from: JString) -> Result<String, MediaError> {
     Ok(env.get_string(&from)?.into())
 }
 
+/// 设置全局资源限制, 启动时调用一次.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_Media_configureLimits(mut env: JNIEnv, _class: JClass, query_params: JString) {
+    guard(&mut env, "Media.configureLimits", (), |env| configure_limits(env, query_params))
+}
+
+fn configure_limits(env: &mut JNIEnv, query_params: JString) -> Result<(), MediaError> {
+    let query_params = get_string(env, query_params)?;
+    image_limits::set_limits(image_limits::Limits::new(&image_style::query_map(&query_params)?)?);
+    Ok(())
+}
+
+/// 设置全局文件沙箱, 启动时调用一次.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_Media_configureSandbox(mut env: JNIEnv, _class: JClass, query_params: JString) {
+    guard(&mut env, "Media.configureSandbox", (), |env| configure_sandbox(env, query_params))
+}
+
+fn configure_sandbox(env: &mut JNIEnv, query_params: JString) -> Result<(), MediaError> {
+    let query_params = get_string(env, query_params)?;
+    image_sandbox::set_sandbox(image_sandbox::Sandbox::new(&image_style::query_map(&query_params)?)?);
+    Ok(())
+}
+
+/// 设置全局样式预设 (JSON 或 TOML), 替换已有预设.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_Media_configurePresets(mut env: JNIEnv, _class: JClass, config: JString) {
+    guard(&mut env, "Media.configurePresets", (), |env| configure_presets(env, config))
+}
+
+fn configure_presets(env: &mut JNIEnv, config: JString) -> Result<(), MediaError> {
+    let config = get_string(env, config)?;
+    image_presets::set_presets(image_presets::Presets::parse(&config)?);
+    Ok(())
+}
+
+/// 设置全局处理结果缓存, 替换已有配置, 目录中已有的结果继续使用.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_Media_configureCache(mut env: JNIEnv, _class: JClass, query_params: JString) {
+    guard(&mut env, "Media.configureCache", (), |env| configure_cache(env, query_params))
+}
+
+fn configure_cache(env: &mut JNIEnv, query_params: JString) -> Result<(), MediaError> {
+    let query_params = get_string(env, query_params)?;
+    image_cache::configure(image_cache::CacheConfig::new(&image_style::query_map(&query_params)?)?)
+}
+
+/// 设置全局解码缓存, 同一张源图片生成多个样式时只解码一次.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_Media_configureDecodeCache(mut env: JNIEnv, _class: JClass, query_params: JString) {
+    guard(&mut env, "Media.configureDecodeCache", (), |env| configure_decode_cache(env, query_params))
+}
+
+fn configure_decode_cache(env: &mut JNIEnv, query_params: JString) -> Result<(), MediaError> {
+    let query_params = get_string(env, query_params)?;
+    image_decode_cache::set_config(image_decode_cache::DecodeCacheConfig::new(&image_style::query_map(&query_params)?)?);
+    Ok(())
+}
+
+/// 缓存统计 [命中, 未命中, 合并, 淘汰, 结果数量, 结果字节数].
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_Media_cacheStats(mut env: JNIEnv, _class: JClass) -> jlongArray {
+    guard(&mut env, "Media.cacheStats", null_mut(), |env| {
+        let stats = image_cache::stats();
+        let values = [stats.hits, stats.misses, stats.coalesced, stats.evictions, stats.entries, stats.bytes];
+        let output = env.new_long_array(values.len() as i32)?;
+        env.set_long_array_region(&output, 0, &values.map(|value| value as i64))?;
+        Ok(output.into_raw())
+    })
+}
+
+/// 释放 MediaBuffer 的内存.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaBuffer_free(mut env: JNIEnv, _class: JClass, address: jlong, length: jlong) {
+    guard(&mut env, "MediaBuffer.free", (), |_| {
+        free_buffer(address as *mut u8, length as usize);
+        Ok(())
+    })
+}
+
 /// 编码通过路径.
 #[no_mangle]
 pub extern "C" fn Java_com_media_image_WebP_encoded(mut env: JNIEnv, _class: JClass,
                                                     from: JString, to: JString, quality: jfloat) {
-    match encoded(&mut env, from, to, quality) {
-        Err(message) => {
-            let message = message.to_string();
-            env.throw(&*message).expect("webp encoded system error");
-        }
-        _ => {}
-    }
+    guard(&mut env, "WebP.encoded", (), |env| encoded(env, from, to, quality))
 }
 
-fn encoded(mut env: &mut JNIEnv, from: JString, to: JString, quality: jfloat) -> Result<(), MediaError> {
+fn encoded(env: &mut JNIEnv, from: JString, to: JString, quality: jfloat) -> Result<(), MediaError> {
     // 读取参数
-    let from: String = get_string(&mut env, from)?;
-    let to: String = get_string(&mut env, to)?;
-
-    // 文件信息不能大于 20MB
-    let metadata = fs::metadata(&from)?;
-    if metadata.len() > 1 {
-        return Err(MediaError::Error("File exceeds 20MB".to_owned()));
-    }
+    let from: String = get_string(env, from)?;
+    let to: String = get_string(env, to)?;
 
-    // 读取源文件
-    let image = image::open(&from)?;
+    // 读取源文件 (按照沙箱检查路径, 按照全局限制检查大小)
+    let image = image_sandbox::read_file(&from)?;
 
     // 执行压缩
-    let result = image_webp::encoded_webp(image, quality)?.to_owned();
+    let options = image_webp::WebPOptions { quality, ..image_webp::WebPOptions::default() };
+    let result = image_webp::encoded_webp_from(&image, Some(&from), &options, StripPolicy::All, &ColorOptions::default())?;
 
     // 输出
-    fs::write(&to, &result)?;
+    image_sandbox::write_file(&to, &result)?;
 
     Ok(())
 }
@@ -75,32 +226,70 @@ fn encoded(mut env: &mut JNIEnv, from: JString, to: JString, quality: jfloat) ->
 #[no_mangle]
 pub extern "C" fn Java_com_media_image_WebP_encodedByBytes(mut env: JNIEnv, _class: JClass,
                                                            from: JByteArray, quality: jfloat) -> jbyteArray {
-    match encoded_by_bytes(&mut env, from, quality) {
-        Ok(value) => {
-            value
-        }
-        Err(message) => {
-            let message = message.to_string();
-            env.throw(&*message).expect("webp encoded system error");
-            null_mut()
-        }
-    }
+    guard(&mut env, "WebP.encodedByBytes", null_mut(), |env| encoded_by_bytes(env, from, quality))
 }
 
 fn encoded_by_bytes(env: &JNIEnv, from: JByteArray, quality: jfloat) -> Result<jbyteArray, MediaError> {
     // 读取图片
-    let from_buffer = get_vec(&env, from)?
-        .ok_or(MediaError::Error("from not found".to_string()))?;
+    let from_buffer = get_vec(env, from)?
+        .ok_or(MediaError::InvalidParam("from not found".to_string()))?;
 
-    // 读取源文件
-    let image = image::load_from_memory(&from_buffer)?;
+    // 执行压缩
+    let options = image_webp::WebPOptions { quality, ..image_webp::WebPOptions::default() };
+    let result = image_webp::encoded_webp_from(&from_buffer, None::<&str>, &options, StripPolicy::All, &ColorOptions::default())?;
+
+    // 返回结果
+    let output_array = env.byte_array_from_slice(&result)?;
+    Ok(output_array.into_raw())
+}
+
+
+/// 编码通过 direct ByteBuffer, 返回 MediaBuffer.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_WebP_encodedByBuffer(mut env: JNIEnv, _class: JClass,
+                                                            from: JByteBuffer, quality: jfloat) -> jobject {
+    guard(&mut env, "WebP.encodedByBuffer", null_mut(), |env| encoded_by_buffer(env, from, quality))
+}
+
+fn encoded_by_buffer(env: &mut JNIEnv, from: JByteBuffer, quality: jfloat) -> Result<jobject, MediaError> {
+    // 读取图片
+    let from_buffer = get_buffer(env, &from)?
+        .ok_or(MediaError::InvalidParam("from not found".to_string()))?;
 
     // 执行压缩
-    let result = image_webp::encoded_webp(image, quality)?.to_owned();
+    let options = image_webp::WebPOptions { quality, ..image_webp::WebPOptions::default() };
+    let result = image_webp::encoded_webp_from(from_buffer, None::<&str>, &options, StripPolicy::All, &ColorOptions::default())?;
+
+    // 返回结果
+    new_media_buffer(env, result)
+}
+
+
+/// 编码通过二进制, 使用完整的 WebP 编码参数.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_WebP_encodedByBytesWithParams(mut env: JNIEnv, _class: JClass,
+                                                                     from: JByteArray, query_params: JString) -> jbyteArray {
+    guard(&mut env, "WebP.encodedByBytesWithParams", null_mut(), |env| encoded_by_bytes_with_params(env, from, query_params))
+}
+
+fn encoded_by_bytes_with_params(env: &mut JNIEnv, from: JByteArray, query_params: JString) -> Result<jbyteArray, MediaError> {
+    // 读取参数
+    let query_params = get_string(env, query_params)?;
+    let query_params_map = image_style::query_map(&query_params)?;
+    let options = image_webp::WebPOptions::new(&query_params_map)?;
+    let strip = StripPolicy::new(&query_params_map)?;
+    let color = ColorOptions::new(&query_params_map)?;
+
+    // 读取图片
+    let from_buffer = get_vec(env, from)?
+        .ok_or(MediaError::InvalidParam("from not found".to_string()))?;
+
+    // 执行压缩
+    let result = image_webp::encoded_webp_from(&from_buffer, None::<&str>, &options, strip, &color)?;
 
     // 返回结果
     let output_array = env.byte_array_from_slice(&result)?;
-    return Ok(output_array.into_raw());
+    Ok(output_array.into_raw())
 }
 
 
@@ -108,34 +297,28 @@ fn encoded_by_bytes(env: &JNIEnv, from: JByteArray, quality: jfloat) -> Result<j
 #[no_mangle]
 pub extern "C" fn Java_com_media_image_WebP_decode(mut env: JNIEnv, _class: JClass,
                                                    from: JString, to: JString) {
-    match decode(&mut env, from, to) {
-        Err(message) => {
-            let message = message.to_string();
-            env.throw(&*message).expect("webp decode system error");
-        }
-        _ => {}
-    }
+    guard(&mut env, "WebP.decode", (), |env| decode(env, from, to))
 }
 
-fn decode(mut env: &mut JNIEnv, from: JString, to: JString) -> Result<(), MediaError> {
+fn decode(env: &mut JNIEnv, from: JString, to: JString) -> Result<(), MediaError> {
     // 读取参数
-    let from: String = get_string(&mut env, from)?;
-    let to: String = get_string(&mut env, to)?;
+    let from: String = get_string(env, from)?;
+    let to: String = get_string(env, to)?;
 
-    // 文件信息不能大于 20MB
-    let metadata = fs::metadata(&from)?;
-    if metadata.len() > 1 {
-        return Err(MediaError::Error("File exceeds 20MB".to_owned()));
-    }
+    // 读取源文件 (按照沙箱检查路径, 按照全局限制检查大小)
+    let image = image_sandbox::read_file(from)?;
 
-    // 读取源文件
-    let image = fs::read(from)?;
+    // 动图输出为 GIF 时保留所有帧
+    if let (Ok(OutputFormat::Gif), Some(animation)) = (OutputFormat::from_path(&to), image_animation::decode(&image)?) {
+        image_sandbox::write_file(&to, &image_animation::encode(&animation, OutputFormat::Gif, &EncodeOptions::default())?)?;
+        return Ok(());
+    }
 
     // 执行转码
     let result = image_webp::decode_webp(image)?.to_owned();
 
     // 输出图片
-    result.save(to)?;
+    result.save(image_sandbox::sandbox().check_write(&to)?)?;
 
     Ok(())
 }
@@ -145,22 +328,13 @@ fn decode(mut env: &mut JNIEnv, from: JString, to: JString) -> Result<(), MediaE
 #[no_mangle]
 pub extern "C" fn Java_com_media_image_WebP_decodeByBytes(mut env: JNIEnv, _class: JClass,
                                                           from: JByteArray) -> jbyteArray {
-    match decode_by_bytes(&mut env, from) {
-        Ok(value) => {
-            value
-        }
-        Err(message) => {
-            let message = message.to_string();
-            env.throw(&*message).expect("webp decode system error");
-            null_mut()
-        }
-    }
+    guard(&mut env, "WebP.decodeByBytes", null_mut(), |env| decode_by_bytes(env, from))
 }
 
 fn decode_by_bytes(env: &mut JNIEnv, from: JByteArray) -> Result<jbyteArray, MediaError> {
     // 读取图片
-    let from_buffer = get_vec(&env, from)?
-        .ok_or(MediaError::Error("from not found".to_string()))?;
+    let from_buffer = get_vec(env, from)?
+        .ok_or(MediaError::InvalidParam("from not found".to_string()))?;
 
     // 执行转码
     let result = image_webp::decode_webp(from_buffer)?.to_owned();
@@ -169,7 +343,7 @@ fn decode_by_bytes(env: &mut JNIEnv, from: JByteArray) -> Result<jbyteArray, Med
     let mut buffer = Cursor::new(Vec::new());
     result.write_to(&mut buffer, image::ImageOutputFormat::Png)?;
     let output_array = env.byte_array_from_slice(buffer.get_ref())?;
-    return Ok(output_array.into_raw());
+    Ok(output_array.into_raw())
 }
 
 
@@ -178,56 +352,60 @@ fn decode_by_bytes(env: &mut JNIEnv, from: JByteArray) -> Result<jbyteArray, Med
 pub extern "C" fn Java_com_media_image_QrCode_generate(mut env: JNIEnv, _class: JClass,
                                                        query_params: JString, logo: JByteArray,
                                                        poster: JByteArray) -> jbyteArray {
-    match qr_code_generate(&mut env, query_params, logo, poster) {
-        Ok(value) => {
-            value
-        }
-        Err(message) => {
-            let message = message.to_string();
-            env.throw(&*message).expect("qrCode system error");
-            null_mut()
-        }
-    }
+    guard(&mut env, "QrCode.generate", null_mut(), |env| qr_code_generate(env, query_params, logo, poster))
 }
 
-fn qr_code_generate(mut env: &mut JNIEnv, query_params: JString, logo: JByteArray, poster: JByteArray) -> Result<jbyteArray, MediaError> {
-    let query_params = get_string(&mut env, query_params)?;
-    let logo = get_vec(&env, logo)?;
-    let poster = get_vec(&env, poster)?;
-    let qr_code = image_qr_code::new(query_params, logo, poster)?;
+fn qr_code_generate(env: &mut JNIEnv, query_params: JString, logo: JByteArray, poster: JByteArray) -> Result<jbyteArray, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let logo = get_vec(env, logo)?;
+    let poster = get_vec(env, poster)?;
 
     // 生成图片
-    let result = qr_code.generate()?;
+    let result = qr_code_png(query_params, logo.as_deref(), poster.as_deref())?;
 
     // 返回结果
-    let mut buffer = Cursor::new(Vec::new());
-    result.write_to(&mut buffer, image::ImageOutputFormat::Png)?;
-    let output_array = env.byte_array_from_slice(buffer.get_ref())?;
-    return Ok(output_array.into_raw());
+    let output_array = env.byte_array_from_slice(&result)?;
+    Ok(output_array.into_raw())
+}
+
+/// 二维码生成, 通过 direct ByteBuffer 传入 logo、海报并返回 MediaBuffer.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_QrCode_generateByBuffer(mut env: JNIEnv, _class: JClass,
+                                                               query_params: JString, logo: JByteBuffer,
+                                                               poster: JByteBuffer) -> jobject {
+    guard(&mut env, "QrCode.generateByBuffer", null_mut(), |env| qr_code_generate_by_buffer(env, query_params, logo, poster))
+}
+
+fn qr_code_generate_by_buffer(env: &mut JNIEnv, query_params: JString, logo: JByteBuffer, poster: JByteBuffer) -> Result<jobject, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let logo = get_buffer(env, &logo)?;
+    let poster = get_buffer(env, &poster)?;
+
+    // 生成图片
+    let result = qr_code_png(query_params, logo, poster)?;
+
+    // 返回结果
+    new_media_buffer(env, result)
+}
+
+/// 生成二维码 PNG.
+fn qr_code_png(query_params: String, logo: Option<&[u8]>, poster: Option<&[u8]>) -> Result<Vec<u8>, MediaError> {
+    image_qr_code::new(query_params, logo, poster)?.generate_png()
 }
 
 
 #[no_mangle]
 pub extern "C" fn Java_com_media_image_ImageStyle_handle(mut env: JNIEnv, _class: JClass,
                                                          image: JByteArray, query_params: JString) -> jbyteArray {
-    match image_style_handle(&mut env, image, query_params) {
-        Ok(value) => {
-            value
-        }
-        Err(message) => {
-            let message = message.to_string();
-            env.throw(&*message).expect("image style system error");
-            null_mut()
-        }
-    }
+    guard(&mut env, "ImageStyle.handle", null_mut(), |env| image_style_handle(env, image, query_params))
 }
 
-fn image_style_handle(mut env: &mut JNIEnv, image: JByteArray, query_params: JString) -> Result<jbyteArray, MediaError> {
-    let query_params = get_string(&mut env, query_params)?;
-    let image = get_vec(&env, image)?;
+fn image_style_handle(env: &mut JNIEnv, image: JByteArray, query_params: JString) -> Result<jbyteArray, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let image = get_vec(env, image)?;
 
     // 生成图片
-    let result = image_style::edit(image, &query_params)?;
+    let result = image_style::edit(image.as_deref(), &query_params)?;
 
     // 返回结果
     match result {
@@ -241,37 +419,243 @@ fn image_style_handle(mut env: &mut JNIEnv, image: JByteArray, query_params: JSt
     }
 }
 
+/// 处理图片, 通过 direct ByteBuffer 传入图片并返回 MediaBuffer (传入 to 时返回 null).
 #[no_mangle]
-pub extern "C" fn Java_com_media_image_ImageStyle_imageMetadata(mut env: JNIEnv, _class: JClass, path: JString) -> jstring {
-    match image_metadata(&mut env, path) {
-        Ok(value) => {
-            value
-        }
-        Err(message) => {
-            let message = message.to_string();
-            env.throw(&*message).expect("image style system error");
-            null_mut()
-        }
+pub extern "C" fn Java_com_media_image_ImageStyle_handleByBuffer(mut env: JNIEnv, _class: JClass,
+                                                                 image: JByteBuffer, query_params: JString) -> jobject {
+    guard(&mut env, "ImageStyle.handleByBuffer", null_mut(), |env| image_style_handle_by_buffer(env, image, query_params))
+}
+
+fn image_style_handle_by_buffer(env: &mut JNIEnv, image: JByteBuffer, query_params: JString) -> Result<jobject, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let image = get_buffer(env, &image)?;
+
+    // 生成图片
+    let result = image_style::edit(image, &query_params)?;
+
+    // 返回结果
+    match result {
+        None => Ok(null_mut()),
+        Some(value) => new_media_buffer(env, value),
     }
 }
 
-fn image_metadata(mut env: &mut JNIEnv, path: JString) -> Result<jstring, MediaError> {
-    let path = get_string(&mut env, path)?;
+/// 处理图片并返回结果对象 (包含目标大小模式使用的质量).
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_ImageStyle_handleWithResult(mut env: JNIEnv, _class: JClass,
+                                                                   image: JByteArray, query_params: JString) -> jobject {
+    guard(&mut env, "ImageStyle.handleWithResult", null_mut(), |env| image_style_handle_with_result(env, image, query_params))
+}
+
+fn image_style_handle_with_result(env: &mut JNIEnv, image: JByteArray, query_params: JString) -> Result<jobject, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let image = get_vec(env, image)?;
+
+    // 生成图片
+    let result = image_style::edit_with_result(image.as_deref(), &query_params)?;
+
+    // 返回结果
+    new_style_result(env, &result)
+}
+
+/// 创建 Java StyleResult.
+fn new_style_result(env: &mut JNIEnv, result: &image_style::EditResult) -> Result<jobject, MediaError> {
+    let data = match &result.data {
+        None => JObject::null(),
+        Some(value) => JObject::from(env.byte_array_from_slice(value)?),
+    };
+    let quality = match result.quality {
+        None => JObject::null(),
+        Some(value) => env.new_object("java/lang/Float", "(F)V", &[JValue::Float(value)])?,
+    };
+    let output = env.new_object("com/media/image/StyleResult", "([BLjava/lang/Float;II)V", &[
+        JValue::Object(&data),
+        JValue::Object(&quality),
+        JValue::Int(result.width as i32),
+        JValue::Int(result.height as i32),
+    ])?;
+    Ok(output.into_raw())
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_ImageStyle_imageMetadata(mut env: JNIEnv, _class: JClass, path: JString) -> jstring {
+    guard(&mut env, "ImageStyle.imageMetadata", null_mut(), |env| image_metadata(env, path))
+}
+
+fn image_metadata(env: &mut JNIEnv, path: JString) -> Result<jstring, MediaError> {
+    let path = get_string(env, path)?;
 
     // 读取元信息
-    let image = image::open(path)?;
-    let image_info = image_style::metadata(image);
+    let (image, orientation) = image_decode::open_with_orientation(path)?;
+    let image_info = image_style::metadata(image, orientation);
     let mut result = String::new();
     for item in image_info {
         result.push_str(item.0);
-        result.push_str("=");
-        result.push_str(&*item.1);
-        result.push_str("&");
+        result.push('=');
+        result.push_str(&item.1);
+        result.push('&');
+    }
+
+    Ok(env.new_string(result)?.into_raw())
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_ImageStyle_imageMetadataJson(mut env: JNIEnv, _class: JClass, path: JString) -> jstring {
+    guard(&mut env, "ImageStyle.imageMetadataJson", null_mut(), |env| image_metadata_json(env, path))
+}
+
+fn image_metadata_json(env: &mut JNIEnv, path: JString) -> Result<jstring, MediaError> {
+    let path = get_string(env, path)?;
+
+    // 读取元信息
+    let metadata = image_metadata::read(&image_sandbox::read_file(&path)?, Some(&path))?;
+    Ok(env.new_string(metadata.to_json()?)?.into_raw())
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_ImageStyle_imageMetadataJsonByBytes(mut env: JNIEnv, _class: JClass, image: JByteArray) -> jstring {
+    guard(&mut env, "ImageStyle.imageMetadataJsonByBytes", null_mut(), |env| image_metadata_json_by_bytes(env, image))
+}
+
+fn image_metadata_json_by_bytes(env: &mut JNIEnv, image: JByteArray) -> Result<jstring, MediaError> {
+    let image = get_vec(env, image)?
+        .ok_or(MediaError::InvalidParam("image not found".to_string()))?;
+
+    // 读取元信息
+    let metadata = image_metadata::read(&image, None::<&str>)?;
+    Ok(env.new_string(metadata.to_json()?)?.into_raw())
+}
+
+/// 一次解码生成多个输出.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_ImageStyle_handleVariants(mut env: JNIEnv, _class: JClass, image: JByteArray,
+                                                                 query_params: JString, variants: JObjectArray) -> jobjectArray {
+    guard(&mut env, "ImageStyle.handleVariants", null_mut(), |env| image_style_handle_variants(env, image, query_params, variants))
+}
+
+fn image_style_handle_variants(env: &mut JNIEnv, image: JByteArray, query_params: JString, variants: JObjectArray) -> Result<jobjectArray, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let image = get_vec(env, image)?;
+    let mut specs = Vec::new();
+    for index in 0..env.get_array_length(&variants)? {
+        let spec = JString::from(env.get_object_array_element(&variants, index)?);
+        specs.push(get_string(env, spec)?);
     }
 
-    return Ok(env.new_string(result)?.into_raw());
+    // 生成图片
+    let results = image_variants::variants(image.as_deref(), &query_params, &specs)?;
+
+    // 返回结果
+    let output = env.new_object_array(results.len() as i32, "com/media/image/StyleResult", JObject::null())?;
+    for (index, result) in results.iter().enumerate() {
+        env.with_local_frame(8, |env| -> Result<(), MediaError> {
+            let element = unsafe { JObject::from_raw(new_style_result(env, result)?) };
+            env.set_object_array_element(&output, index as i32, element)?;
+            Ok(())
+        })?;
+    }
+    Ok(output.into_raw())
+}
+
+/// 解码图片, 返回图片 id.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_decode(mut env: JNIEnv, _class: JClass,
+                                                        image: JByteArray, query_params: JString) -> jlong {
+    guard(&mut env, "MediaImage.decode", 0, |env| media_image_decode(env, image, query_params))
 }
 
+fn media_image_decode(env: &mut JNIEnv, image: JByteArray, query_params: JString) -> Result<jlong, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let image = get_vec(env, image)?
+        .ok_or(MediaError::InvalidParam("image not found".to_string()))?;
+    let handle = image_handle::ImageHandle::open(&image, None, &image_style::query_map(&query_params)?)?;
+    Ok(image_handle::insert(handle))
+}
+
+/// 解码 direct ByteBuffer 中的图片, 返回图片 id.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_decodeByBuffer(mut env: JNIEnv, _class: JClass,
+                                                                image: JByteBuffer, query_params: JString) -> jlong {
+    guard(&mut env, "MediaImage.decodeByBuffer", 0, |env| media_image_decode_by_buffer(env, image, query_params))
+}
+
+fn media_image_decode_by_buffer(env: &mut JNIEnv, image: JByteBuffer, query_params: JString) -> Result<jlong, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let image = get_buffer(env, &image)?
+        .ok_or(MediaError::InvalidParam("image not found".to_string()))?;
+    let handle = image_handle::ImageHandle::open(image, None, &image_style::query_map(&query_params)?)?;
+    Ok(image_handle::insert(handle))
+}
+
+/// 解码图片文件, 返回图片 id.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_decodeFile(mut env: JNIEnv, _class: JClass,
+                                                            path: JString, query_params: JString) -> jlong {
+    guard(&mut env, "MediaImage.decodeFile", 0, |env| media_image_decode_file(env, path, query_params))
+}
+
+fn media_image_decode_file(env: &mut JNIEnv, path: JString, query_params: JString) -> Result<jlong, MediaError> {
+    let path = get_string(env, path)?;
+    let query_params = get_string(env, query_params)?;
+    let image = image_sandbox::read_file(&path)?;
+    let handle = image_handle::ImageHandle::open(&image, Some(&path), &image_style::query_map(&query_params)?)?;
+    Ok(image_handle::insert(handle))
+}
+
+/// 执行样式操作, 返回新的图片 id.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_apply(mut env: JNIEnv, _class: JClass,
+                                                       handle: jlong, query_params: JString) -> jlong {
+    guard(&mut env, "MediaImage.apply", 0, |env| media_image_apply(env, handle, query_params))
+}
+
+fn media_image_apply(env: &mut JNIEnv, handle: jlong, query_params: JString) -> Result<jlong, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let image = image_handle::get(handle)?;
+    Ok(image_handle::insert(image.style(&image_style::query_map(&query_params)?)?))
+}
+
+/// 编码图片并返回结果对象.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_encodeWithResult(mut env: JNIEnv, _class: JClass,
+                                                                  handle: jlong, query_params: JString) -> jobject {
+    guard(&mut env, "MediaImage.encodeWithResult", null_mut(), |env| media_image_encode_with_result(env, handle, query_params))
+}
+
+fn media_image_encode_with_result(env: &mut JNIEnv, handle: jlong, query_params: JString) -> Result<jobject, MediaError> {
+    let query_params = get_string(env, query_params)?;
+    let result = image_handle::get(handle)?.encode(&image_style::query_map(&query_params)?)?;
+    new_style_result(env, &result)
+}
+
+/// 源图片元信息 (JSON).
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_metadata(mut env: JNIEnv, _class: JClass, handle: jlong) -> jstring {
+    guard(&mut env, "MediaImage.metadata", null_mut(), |env| {
+        let image = image_handle::get(handle)?;
+        Ok(env.new_string(image.metadata_json())?.into_raw())
+    })
+}
+
+/// 当前宽高 [宽, 高].
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_dimensions(mut env: JNIEnv, _class: JClass, handle: jlong) -> jintArray {
+    guard(&mut env, "MediaImage.dimensions", null_mut(), |env| {
+        let (width, height) = image_handle::get(handle)?.dimensions();
+        let output = env.new_int_array(2)?;
+        env.set_int_array_region(&output, 0, &[width as i32, height as i32])?;
+        Ok(output.into_raw())
+    })
+}
+
+/// 关闭图片.
+#[no_mangle]
+pub extern "C" fn Java_com_media_image_MediaImage_release(mut env: JNIEnv, _class: JClass, handle: jlong) {
+    guard(&mut env, "MediaImage.release", (), |_| {
+        image_handle::remove(handle);
+        Ok(())
+    })
+}
 
 #[cfg(test)]
 mod tests {